@@ -24,7 +24,7 @@ fn main() {
     let gitignore_entry = IndexEntry {
         time: SystemTime::now(),
         mtime: SystemTime::now(),
-        dev: "".to_string(),
+        dev: 0,
         ino: 0,
         model_type: ModelType::Regular,
         model_perms: 0,
@@ -33,7 +33,9 @@ fn main() {
         fsize: 0,
         sha: gitignore_sha,
         flag_assume_valid: false,
-        flag_stage: false,
+        flag_stage: 0,
+        flag_skip_worktree: false,
+        flag_intent_to_add: false,
         name: "C:\\Users\\benja\\Documents\\code\\my_git_test\\.gitignore".to_string()
     };
 
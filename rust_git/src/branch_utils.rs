@@ -1,8 +1,14 @@
-use std::fs;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use crate::commit_graph::is_ancestor;
 use crate::file_utils::repo_file;
+use crate::object_utils::object_find;
+use crate::refs::{Ref, RefType};
 use crate::repository::Repository;
 
 const HEAD_REF: &str = "ref: refs/heads/";
+const HEADS_PREFIX: &str = "refs/heads/";
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
 
 pub fn branch_get_active(repo: &Repository) -> Option<String> {
     get_head_contents(&repo).and_then(|head_contents| {
@@ -16,6 +22,183 @@ pub fn branch_get_active(repo: &Repository) -> Option<String> {
     }).ok()
 }
 
+/// The names of all the branches in the repo, sorted
+pub fn branch_list(repo: &Repository) -> Vec<String> {
+    let mut branches: Vec<String> = Ref::all_refs(repo).into_iter()
+        .filter_map(|reference| reference.name.strip_prefix(HEADS_PREFIX).map(|name| name.to_string()))
+        .collect();
+
+    branches.sort();
+    branches
+}
+
+/// Create a branch pointing at the commit a revision resolves to, returning the sha of that commit.
+/// An existing branch is only moved if force is set
+pub fn branch_create(repo: &Repository, name: &str, start_point: &str, force: bool) -> Result<String, String> {
+    branch_check_name(name)?;
+
+    let reference = branch_ref(name);
+    if reference.exists(repo) && !force {
+        return Err(format!("A branch named '{}' already exists", name));
+    }
+
+    // moving the current branch would leave the index and worktree out of step with HEAD
+    if force && branch_get_active(repo).as_deref() == Some(name) && reference.exists(repo) {
+        return Err(format!("Cannot force update the current branch '{}'", name));
+    }
+
+    let sha = object_find(repo, start_point, Some("commit"), true)?;
+    branch_write(repo, name, &sha)?;
+
+    Ok(sha)
+}
+
+/// Rename a branch, keeping HEAD pointing at it if it is the current branch
+pub fn branch_rename(repo: &Repository, old_name: &str, new_name: &str, force: bool) -> Result<(), String> {
+    branch_check_name(new_name)?;
+
+    let old_ref = branch_ref(old_name);
+    let sha = match old_ref.fully_resolve(repo) {
+        RefType::Direct(sha) if old_ref.exists(repo) => sha,
+        _ => return Err(format!("No branch named '{}'", old_name))
+    };
+
+    if branch_ref(new_name).exists(repo) && !force {
+        return Err(format!("A branch named '{}' already exists", new_name));
+    }
+
+    old_ref.delete(repo)?;
+    branch_write(repo, new_name, &sha)?;
+
+    if branch_get_active(repo).as_deref() == Some(old_name) {
+        head_set_branch(repo, new_name)?;
+    }
+
+    Ok(())
+}
+
+/// Delete a branch. Unless forced we refuse if its commits are not all reachable from HEAD,
+/// as they would then only be reachable by their sha
+pub fn branch_delete(repo: &Repository, name: &str, force: bool) -> Result<(), String> {
+    let reference = branch_ref(name);
+    let sha = match reference.fully_resolve(repo) {
+        RefType::Direct(sha) if reference.exists(repo) => sha,
+        _ => return Err(format!("No branch named '{}'", name))
+    };
+
+    if branch_get_active(repo).as_deref() == Some(name) {
+        return Err(format!("Cannot delete the branch '{}' which is currently checked out", name));
+    }
+
+    if !force {
+        let head = object_find(repo, "HEAD", Some("commit"), true)?;
+        if !is_ancestor(repo, &sha, &head)? {
+            return Err(format!("The branch '{}' is not fully merged, force the delete to remove it anyway", name));
+        }
+    }
+
+    println!("Deleted branch {} (was {})", name, sha);
+    reference.delete(repo)
+}
+
+/// Point HEAD at a branch, optionally creating it at the current HEAD first. This only moves
+/// HEAD, the index and worktree are left as they are
+pub fn branch_switch(repo: &Repository, name: &str, create: bool) -> Result<(), String> {
+    if create {
+        branch_create(repo, name, "HEAD", false)?;
+    } else if !branch_ref(name).exists(repo) {
+        return Err(format!("No branch named '{}'", name));
+    }
+
+    let from = branch_get_active(repo)
+        .or_else(|| object_find(repo, "HEAD", None, true).ok())
+        .unwrap_or_default();
+
+    head_log_checkout(repo, &from, name)?;
+    head_set_branch(repo, name)
+}
+
+/// Find the branch (or commit, if HEAD was detached) that was checked out n switches ago
+/// by reading the 'checkout: moving from A to B' lines of the HEAD reflog, as git does for @{-n}
+pub fn branch_get_previous(repo: &Repository, n: usize) -> Result<String, String> {
+    let path = repo_file(repo, vec!["logs".to_string(), "HEAD".to_string()], false)?;
+    let log = fs::read_to_string(path).unwrap_or_default();
+
+    log.lines().rev()
+        .filter_map(|line| line.split_once("\tcheckout: moving from "))
+        .filter_map(|(_, message)| message.split_once(" to "))
+        .map(|(from, _)| from.to_string())
+        .nth(n - 1)
+        .ok_or(format!("The reflog does not go back {} checkouts", n))
+}
+
+/// Point HEAD directly at a commit rather than at a branch
+pub fn head_set_detached(repo: &Repository, sha: &str) -> Result<(), String> {
+    repo_file(repo, vec!["HEAD".to_string()], false)
+        .and_then(|path| fs::write(path, format!("{}\n", sha)).map_err(|e| e.to_string()))
+}
+
+/// Move whatever HEAD points at on to a new commit, the tip of the active branch or HEAD itself when detached
+pub fn head_advance(repo: &Repository, sha: &str) -> Result<(), String> {
+    match branch_get_active(repo) {
+        Some(branch) => branch_write(repo, &branch, sha),
+        None => head_set_detached(repo, sha)
+    }
+}
+
+/// Point HEAD at a branch, which may not exist yet if it has no commits
+pub fn head_set_branch(repo: &Repository, name: &str) -> Result<(), String> {
+    repo_file(repo, vec!["HEAD".to_string()], false)
+        .and_then(|path| fs::write(path, format!("{}{}\n", HEAD_REF, name)).map_err(|e| e.to_string()))
+}
+
+/// Append a line to the HEAD reflog recording that we moved HEAD from one branch (or commit)
+/// to another, in the format git uses so that @{-n} can find previous branches
+pub fn head_log_checkout(repo: &Repository, from: &str, to: &str) -> Result<(), String> {
+    let old_sha = match Ref::new("HEAD".to_string()).fully_resolve(repo) {
+        RefType::Direct(sha) => sha,
+        _ => NULL_SHA.to_string()
+    };
+    let new_sha = object_find(repo, to, None, true).unwrap_or(NULL_SHA.to_string());
+
+    let path = repo_file(repo, vec!["logs".to_string(), "HEAD".to_string()], true)?;
+    let mut log = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+
+    writeln!(log, "{} {} {}\tcheckout: moving from {} to {}", old_sha, new_sha, repo.conf.signature(), from, to)
+        .map_err(|e| e.to_string())
+}
+
+fn branch_ref(name: &str) -> Ref {
+    Ref::new(format!("{}{}", HEADS_PREFIX, name))
+}
+
+/// Point a branch at a commit, creating it if needed
+pub fn branch_write(repo: &Repository, name: &str, sha: &str) -> Result<(), String> {
+    let mut reference = branch_ref(name);
+    reference.add_target(RefType::Direct(sha.to_string()));
+    reference.write(repo)
+}
+
+/// A subset of the rules of git check-ref-format, enough to keep names usable in revisions
+fn branch_check_name(name: &str) -> Result<(), String> {
+    let invalid = name.is_empty()
+        || name.starts_with('-')
+        || name.starts_with('/')
+        || name.ends_with('/')
+        || name.ends_with(".lock")
+        || name.contains("..")
+        || name.contains("//")
+        || name.contains("@{")
+        || name == "HEAD"
+        || name.chars().any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+
+    if invalid {
+        Err(format!("'{}' is not a valid branch name", name))
+    } else {
+        Ok(())
+    }
+}
+
 fn get_head_contents(repo: &Repository) -> Result<String, String> {
     repo_file(&repo, vec!["HEAD".to_string()], false)
         .map_err(|e| e.to_owned())
@@ -24,8 +207,9 @@ fn get_head_contents(repo: &Repository) -> Result<String, String> {
 
 #[cfg(test)]
 mod test {
+    use std::fs;
     use tempdir::TempDir;
-    use crate::branch_utils::branch_get_active;
+    use crate::branch_utils::{branch_create, branch_delete, branch_get_active, branch_get_previous, branch_list, branch_rename, branch_switch};
     use crate::repository::Repository;
 
     #[test]
@@ -41,4 +225,47 @@ mod test {
         let branch = branch_get_active(&repo.unwrap());
         assert_eq!(branch, Some("master".to_string()));
     }
+
+    #[test]
+    fn test_branch_create_rename_delete() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_sha = repo.commit("First commit".to_string()).unwrap();
+
+        assert_eq!(branch_create(&repo, "feature/one", "HEAD", false), Ok(first_sha.clone()));
+        assert!(branch_create(&repo, "feature/one", "HEAD", false).is_err());
+        assert!(branch_create(&repo, "bad..name", "HEAD", false).is_err());
+        assert_eq!(branch_list(&repo), vec!["feature/one", "master"]);
+
+        // renaming the current branch keeps HEAD on it
+        assert!(branch_rename(&repo, "master", "main", false).is_ok());
+        assert_eq!(branch_get_active(&repo), Some("main".to_string()));
+        assert_eq!(branch_list(&repo), vec!["feature/one", "main"]);
+
+        // commit on the feature branch so that it is no longer merged into main
+        assert!(branch_switch(&repo, "feature/one", false).is_ok());
+        assert_eq!(branch_get_active(&repo), Some("feature/one".to_string()));
+        fs::write(tmp_dir.path().join("first.txt"), "feature change").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        repo.commit("Feature commit".to_string()).unwrap();
+
+        assert!(branch_delete(&repo, "feature/one", false).is_err());
+        assert!(branch_switch(&repo, "main", false).is_ok());
+        assert_eq!(branch_get_previous(&repo, 1), Ok("feature/one".to_string()));
+        assert!(branch_delete(&repo, "main", true).is_err());
+        assert!(branch_delete(&repo, "feature/one", false).is_err());
+        assert!(branch_delete(&repo, "feature/one", true).is_ok());
+        assert_eq!(branch_list(&repo), vec!["main"]);
+
+        // a merged branch can be deleted without forcing
+        assert!(branch_switch(&repo, "merged", true).is_ok());
+        assert!(branch_switch(&repo, "main", false).is_ok());
+        assert!(branch_delete(&repo, "merged", false).is_ok());
+
+        tmp_dir.close().unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,499 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use crate::branch_utils::{branch_get_active, branch_switch, head_log_checkout, head_set_detached};
+use crate::git_object::{GitObject, EXECUTABLE_MODE, SYMLINK_MODE};
+use crate::index::{Index, IndexEntry};
+use crate::object_utils::{object_find, object_read, tree_to_dict, tree_to_entries, tree_to_modes};
+use crate::refs::Ref;
+use crate::repository::Repository;
+
+/// Checkout a branch or commit into the current worktree.
+///
+/// Only the files which differ between the trees of HEAD and the target are touched, and their
+/// index entries are rewritten to match. A branch becomes the new symbolic HEAD, anything else
+/// leaves HEAD detached at the commit.
+///
+/// We refuse to run if any of the files we would change has staged or unstaged modifications,
+/// or is an untracked file which would be overwritten, so that no local work is lost
+pub fn checkout_in_place(repo: &Repository, target: &str) -> Result<(), String> {
+    let target_sha = object_find(repo, target, Some("commit"), true)?;
+
+    checkout_tree(repo, &target_sha)?;
+    checkout_move_head(repo, target, &target_sha)
+}
+
+/// Bring the worktree and index from the tree of HEAD to the tree of another commit without moving
+/// HEAD itself, refusing if that would lose local changes
+pub fn checkout_tree(repo: &Repository, target_sha: &str) -> Result<(), String> {
+    let head_tree = tree_to_entries(repo, "HEAD")?;
+    let target_tree = tree_to_entries(repo, target_sha)?;
+    let mut index = Index::read(repo)?;
+
+    let changed = checkout_changed_paths(&head_tree, &target_tree);
+    checkout_check_local_changes(repo, &index, &changed, &head_tree, &target_tree)?;
+
+    for path in &changed {
+        index.remove_entry(path);
+
+        match target_tree.get(path) {
+            Some((sha, mode)) => {
+                println!("Updating {}", path);
+                checkout_write_blob(repo, sha, path, Some(mode))?;
+                index.add_entry(IndexEntry::from_worktree(repo, sha.clone(), path.clone()));
+            },
+            None => {
+                println!("Removing {}", path);
+                repo.delete_worktree_file(path)?;
+            }
+        }
+    }
+
+    index.sort();
+    index.write(repo)
+}
+
+/// Restore the given paths to the version in a source, which is how git undoes changes to single files.
+///
+/// With worktree set the files are overwritten with the source version, with staged set their index
+/// entries are reset to it (so the change is unstaged). If no source revision is given the worktree is
+/// restored from the index and the index from HEAD, as in git restore
+pub fn restore_paths(repo: &Repository, paths: Vec<String>, staged: bool, worktree: bool, source: Option<&str>) -> Result<(), String> {
+    let mut index = Index::read(repo)?;
+
+    let (source_tree, source_modes): (HashMap<String, String>, HashMap<String, String>) = match source {
+        Some(rev) => {
+            let tree = object_find(repo, rev, Some("tree"), true)?;
+            (tree_to_dict(repo, &tree, None)?, tree_to_modes(repo, &tree)?)
+        },
+        None if staged => (tree_to_dict(repo, "HEAD", None)?, tree_to_modes(repo, "HEAD")?),
+        None => (
+            index.entries.iter().map(|entry| (entry.name.clone(), entry.sha.clone())).collect(),
+            index.entries.iter().map(|entry| (entry.name.clone(), entry.mode())).collect()
+        )
+    };
+
+    let mut names = BTreeSet::new();
+    for path in paths {
+        let name = repo.worktree_relative_path(&path)?;
+        let dir_prefix = format!("{}/", name);
+
+        // anything in the source or the index under the path, so that files missing from the source are removed
+        let matched: Vec<String> = source_tree.keys()
+            .chain(index.entries.iter().map(|entry| &entry.name))
+            .filter(|candidate| name.is_empty() || **candidate == name || candidate.starts_with(&dir_prefix))
+            .cloned()
+            .collect();
+
+        if matched.is_empty() {
+            return Err(format!("pathspec '{}' did not match any files", path));
+        }
+
+        names.extend(matched);
+    }
+
+    for name in &names {
+        match source_tree.get(name) {
+            Some(sha) => {
+                if worktree {
+                    println!("Restoring {}", name);
+                    checkout_write_blob(repo, sha, name, source_modes.get(name).map(|mode| mode.as_str()))?;
+                }
+
+                if staged {
+                    index.remove_entry(name);
+                    index.add_entry(restore_index_entry(repo, sha, source_modes.get(name), name)?);
+                }
+            },
+            None => {
+                if worktree {
+                    println!("Removing {}", name);
+                    repo.delete_worktree_file(name)?;
+                }
+
+                if staged {
+                    index.remove_entry(name);
+                }
+            }
+        }
+    }
+
+    index.sort();
+    index.write(repo)
+}
+
+/// An index entry for a restored blob, we can only take the metadata from the
+/// worktree file if it actually contains that blob
+fn restore_index_entry(repo: &Repository, sha: &str, mode: Option<&String>, name: &str) -> Result<IndexEntry, String> {
+    let mut entry = if repo.hash_worktree_file(name)?.as_deref() == Some(sha) {
+        IndexEntry::from_worktree(repo, sha.to_string(), name.to_string())
+    } else {
+        IndexEntry::from_blob(sha.to_string(), name.to_string())
+    };
+
+    // the staged mode is the source's, whatever the worktree file has
+    if let Some(mode) = mode {
+        entry.set_mode(mode);
+    }
+
+    Ok(entry)
+}
+
+/// Write the contents of a blob to a path relative to the worktree, creating any parent directories
+pub fn checkout_write_blob(repo: &Repository, sha: &str, path: &str, mode: Option<&str>) -> Result<(), String> {
+    let data = match object_read(repo, sha.to_string())? {
+        GitObject::Blob(blob) => blob.data.unwrap_or_default(),
+        other => return Err(format!("Expected {} to be a blob but found a {}", path, other.format_name()))
+    };
+
+    let full_path = Path::new(&repo.worktree).join(path);
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    // writing to a symlink would write to whatever it points at
+    if full_path.is_symlink() {
+        fs::remove_file(&full_path).map_err(|e| e.to_string())?;
+    }
+
+    match mode {
+        Some(SYMLINK_MODE) => checkout_write_symlink(&data, &full_path),
+        Some(mode) => {
+            fs::write(&full_path, data).map_err(|e| e.to_string())?;
+            checkout_set_executable(&full_path, mode == EXECUTABLE_MODE)
+        },
+        // an existing file keeps its permissions when overwritten
+        None => fs::write(full_path, data).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(unix)]
+fn checkout_write_symlink(target: &[u8], path: &Path) -> Result<(), String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path).map_err(|e| e.to_string())
+}
+
+/// Without symlinks the file holds the path it would point at, as git does with core.symlinks off
+#[cfg(not(unix))]
+fn checkout_write_symlink(target: &[u8], path: &Path) -> Result<(), String> {
+    fs::write(path, target).map_err(|e| e.to_string())
+}
+
+#[cfg(unix)]
+fn checkout_set_executable(path: &Path, executable: bool) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path).map_err(|e| e.to_string())?.permissions();
+    let mode = permissions.mode();
+    // like git, executable for everyone who can read the file
+    permissions.set_mode(if executable { mode | (mode & 0o444) >> 2 } else { mode & !0o111 });
+    fs::set_permissions(path, permissions).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn checkout_set_executable(_path: &Path, _executable: bool) -> Result<(), String> {
+    Ok(())
+}
+
+/// Every path whose sha or mode differs between the two trees, including paths only in one of them
+fn checkout_changed_paths(head_tree: &HashMap<String, (String, String)>, target_tree: &HashMap<String, (String, String)>) -> BTreeSet<String> {
+    head_tree.keys().chain(target_tree.keys())
+        .filter(|path| head_tree.get(*path) != target_tree.get(*path))
+        .cloned()
+        .collect()
+}
+
+fn checkout_check_local_changes(repo: &Repository, index: &Index, changed: &BTreeSet<String>,
+                                head_tree: &HashMap<String, (String, String)>, target_tree: &HashMap<String, (String, String)>) -> Result<(), String> {
+    let mut conflicts = Vec::new();
+
+    for path in changed {
+        let staged = index.get_entry(path).map(|entry| entry.sha.clone());
+        let worktree = repo.hash_worktree_file(path)?;
+        let head_sha = head_tree.get(path).map(|(sha, _)| sha);
+        let target_sha = target_tree.get(path).map(|(sha, _)| sha);
+
+        // Nothing is lost if the file is already in the state we would leave it in
+        if staged.as_ref() == target_sha && worktree.as_ref() == target_sha {
+            continue;
+        }
+
+        if staged.as_ref() != head_sha || worktree != staged {
+            conflicts.push(path.clone());
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Your local changes to the following files would be overwritten by checkout:\n\t{}", conflicts.join("\n\t")))
+    }
+}
+
+fn checkout_move_head(repo: &Repository, target: &str, target_sha: &str) -> Result<(), String> {
+    if Ref::new(format!("refs/heads/{}", target)).exists(repo) {
+        branch_switch(repo, target, false)?;
+        println!("Switched to branch '{}'", target);
+        return Ok(());
+    }
+
+    let from = branch_get_active(repo)
+        .or_else(|| object_find(repo, "HEAD", None, true).ok())
+        .unwrap_or_default();
+
+    head_log_checkout(repo, &from, target_sha)?;
+    head_set_detached(repo, target_sha)?;
+    println!("HEAD is now at {}", target_sha);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::branch_utils::{branch_create, branch_get_active};
+    use crate::checkout::{checkout_in_place, restore_paths};
+    use crate::commit_graph::commit_read;
+    use crate::file_utils::repo_file;
+    use crate::index::Index;
+    use crate::object_utils::{tree_to_dict, tree_to_modes};
+    use crate::repository::Repository;
+    use crate::status::Status;
+
+    #[test]
+    fn checkout_in_place_between_branches() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("second.txt"), "second file").unwrap();
+        fs::write(tmp_dir.path().join("untouched.txt"), "untouched file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_sha = repo.commit("First commit".to_string()).unwrap();
+        branch_create(&repo, "feature", "HEAD", false).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file changed").unwrap();
+        fs::write(tmp_dir.path().join("third.txt"), "third file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.rm(vec![tmp_dir.path().join("second.txt").to_str().unwrap().to_string()], true, false, false).unwrap();
+        repo.commit("Second commit".to_string()).unwrap();
+
+        assert!(checkout_in_place(&repo, "feature").is_ok());
+        assert_eq!(branch_get_active(&repo), Some("feature".to_string()));
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("first.txt")).unwrap(), "first file");
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("second.txt")).unwrap(), "second file");
+        assert!(!tmp_dir.path().join("third.txt").exists());
+
+        let index = Index::read(&repo).unwrap();
+        let names: Vec<String> = index.entries.iter().map(|entry| entry.name.clone()).collect();
+        assert_eq!(names, vec!["first.txt", "second.txt", "untouched.txt"]);
+
+        // an unstaged change to a file the checkout would overwrite stops it
+        fs::write(tmp_dir.path().join("first.txt"), "local change").unwrap();
+        assert!(checkout_in_place(&repo, "master").is_err());
+        assert_eq!(branch_get_active(&repo), Some("feature".to_string()));
+
+        // but a change to a file the checkout doesnt touch is carried over
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("untouched.txt"), "local change").unwrap();
+        assert!(checkout_in_place(&repo, "master").is_ok());
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("untouched.txt")).unwrap(), "local change");
+
+        // an untracked file which would be overwritten also stops it
+        fs::write(tmp_dir.path().join("second.txt"), "untracked").unwrap();
+        assert!(checkout_in_place(&repo, "feature").is_err());
+        fs::remove_file(tmp_dir.path().join("second.txt")).unwrap();
+
+        // a sha leaves HEAD detached
+        assert!(checkout_in_place(&repo, &first_sha).is_ok());
+        assert_eq!(branch_get_active(&repo), None);
+        assert_eq!(fs::read_to_string(tmp_dir.path().join(".git/HEAD")).unwrap(), format!("{}\n", first_sha));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn restore_worktree_and_staged() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        let first_path = tmp_dir.path().join("first.txt").to_str().unwrap().to_string();
+        fs::write(&first_path, "version 1").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_sha = repo.commit("First commit".to_string()).unwrap();
+        let version_1 = Index::read(&repo).unwrap().get_entry("first.txt").unwrap().sha.clone();
+
+        fs::write(&first_path, "version 2").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.commit("Second commit".to_string()).unwrap();
+        let version_2 = Index::read(&repo).unwrap().get_entry("first.txt").unwrap().sha.clone();
+
+        // discard an unstaged change by copying back the staged blob
+        fs::write(&first_path, "unstaged change").unwrap();
+        assert!(restore_paths(&repo, vec![first_path.clone()], false, true, None).is_ok());
+        assert_eq!(fs::read_to_string(&first_path).unwrap(), "version 2");
+
+        // unstage a change, leaving the worktree file alone
+        fs::write(&first_path, "staged change").unwrap();
+        repo.add(vec![first_path.clone()]).unwrap();
+        assert!(restore_paths(&repo, vec![first_path.clone()], true, false, None).is_ok());
+        assert_eq!(Index::read(&repo).unwrap().get_entry("first.txt").unwrap().sha, version_2);
+        assert_eq!(fs::read_to_string(&first_path).unwrap(), "staged change");
+
+        // pull the file from an older commit into both the index and the worktree
+        assert!(restore_paths(&repo, vec![first_path.clone()], true, true, Some(&first_sha)).is_ok());
+        assert_eq!(Index::read(&repo).unwrap().get_entry("first.txt").unwrap().sha, version_1);
+        assert_eq!(fs::read_to_string(&first_path).unwrap(), "version 1");
+
+        // a newly added file is removed from the index when restoring from HEAD
+        let new_path = tmp_dir.path().join("new.txt").to_str().unwrap().to_string();
+        fs::write(&new_path, "new file").unwrap();
+        repo.add(vec![new_path.clone()]).unwrap();
+        assert!(restore_paths(&repo, vec![new_path.clone()], true, false, None).is_ok());
+        assert!(Index::read(&repo).unwrap().get_entry("new.txt").is_none());
+        assert!(tmp_dir.path().join("new.txt").exists());
+
+        assert!(restore_paths(&repo, vec![new_path], false, true, None).is_err());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checkout_executables_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("readme.txt"), "readme").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_sha = repo.commit("Readme".to_string()).unwrap();
+
+        let script = tmp_dir.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("run.sh", tmp_dir.path().join("link")).unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let second_sha = repo.commit("Script and link".to_string()).unwrap();
+
+        // the symlink is stored as the path it points at, not the contents of the script
+        let modes = tree_to_modes(&repo, &second_sha).unwrap();
+        assert_eq!(modes.get("run.sh").map(|mode| mode.as_str()), Some("100755"));
+        assert_eq!(modes.get("link").map(|mode| mode.as_str()), Some("120000"));
+        assert_eq!(repo.hash_worktree_file("link").unwrap(), tree_to_dict(&repo, &second_sha, None).unwrap().get("link").cloned());
+
+        checkout_in_place(&repo, &first_sha).unwrap();
+        assert!(!script.exists() && !tmp_dir.path().join("link").is_symlink());
+
+        checkout_in_place(&repo, &second_sha).unwrap();
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o111, 0o111);
+        assert_eq!(fs::read_link(tmp_dir.path().join("link")).unwrap().to_str(), Some("run.sh"));
+        assert!(Status::read(&repo).unwrap().is_clean());
+
+        // commits which differ only in the mode of a file still swap its executable bit
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o644)).unwrap();
+        repo.add(vec![script.to_str().unwrap().to_string()]).unwrap();
+        let third_sha = repo.commit("Not executable".to_string()).unwrap();
+        checkout_in_place(&repo, &second_sha).unwrap();
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o111, 0o111);
+        checkout_in_place(&repo, &third_sha).unwrap();
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o111, 0);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn checkout_in_place_nested_paths() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::create_dir_all(tmp_dir.path().join("dir/sub")).unwrap();
+        fs::write(tmp_dir.path().join("dir/first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("dir/sub/second.txt"), "second file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.commit("First commit".to_string()).unwrap();
+        branch_create(&repo, "feature", "HEAD", false).unwrap();
+
+        fs::write(tmp_dir.path().join("dir/sub/second.txt"), "second file changed").unwrap();
+        fs::write(tmp_dir.path().join("dir/sub/third.txt"), "third file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.commit("Second commit".to_string()).unwrap();
+
+        assert!(checkout_in_place(&repo, "feature").is_ok());
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("dir/sub/second.txt")).unwrap(), "second file");
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("dir/first.txt")).unwrap(), "first file");
+        assert!(!tmp_dir.path().join("dir/sub/third.txt").exists());
+
+        let index = Index::read(&repo).unwrap();
+        let names: Vec<String> = index.entries.iter().map(|entry| entry.name.clone()).collect();
+        assert_eq!(names, vec!["dir/first.txt", "dir/sub/second.txt"]);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn restore_nested_paths() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::create_dir_all(tmp_dir.path().join("dir/sub")).unwrap();
+        fs::write(tmp_dir.path().join("dir/first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("dir/sub/second.txt"), "second file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.commit("First commit".to_string()).unwrap();
+
+        // a directory restores everything beneath it, from HEAD into the index
+        fs::write(tmp_dir.path().join("dir/sub/second.txt"), "staged change").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let dir_path = tmp_dir.path().join("dir").to_str().unwrap().to_string();
+        assert!(restore_paths(&repo, vec![dir_path.clone()], true, false, None).is_ok());
+        let index = Index::read(&repo).unwrap();
+        let names: Vec<String> = index.entries.iter().map(|entry| entry.name.clone()).collect();
+        assert_eq!(names, vec!["dir/first.txt", "dir/sub/second.txt"]);
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("dir/sub/second.txt")).unwrap(), "staged change");
+
+        // and from the index into the worktree
+        assert!(restore_paths(&repo, vec![dir_path], false, true, None).is_ok());
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("dir/sub/second.txt")).unwrap(), "second file");
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("dir/first.txt")).unwrap(), "first file");
+        assert_eq!(repo.hash_worktree_file("dir/sub/second.txt").unwrap(), index.get_entry("dir/sub/second.txt").map(|entry| entry.sha.clone()));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn checkout_unreadable_tree() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_sha = repo.commit("First commit".to_string()).unwrap();
+        fs::write(tmp_dir.path().join("second.txt"), "second file").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let second_sha = repo.commit("Second commit".to_string()).unwrap();
+
+        // a missing tree is an error rather than an empty tree, which would delete every file
+        let tree = commit_read(&repo, &first_sha).unwrap().get_tree_string().unwrap();
+        fs::remove_file(repo_file(&repo, vec!["objects".to_string(), tree[..2].to_string(), tree[2..].to_string()], false).unwrap()).unwrap();
+        assert!(checkout_in_place(&repo, &first_sha).is_err());
+        assert!(tmp_dir.path().join("first.txt").exists() && tmp_dir.path().join("second.txt").exists());
+        assert!(Status::read(&repo).unwrap().is_clean());
+
+        // nor does status report every file as staged for deletion when HEAD's tree is gone
+        let tree = commit_read(&repo, &second_sha).unwrap().get_tree_string().unwrap();
+        fs::remove_file(repo_file(&repo, vec!["objects".to_string(), tree[..2].to_string(), tree[2..].to_string()], false).unwrap()).unwrap();
+        assert!(Status::read(&repo).is_err());
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::checkout::restore_paths;
+use crate::commit_graph::commit_read;
+use crate::file_utils::repo_file;
+use crate::git_object::GitCommit;
+use crate::merge::{merge_apply, merge_head_read, merge_message_read, merge_trees, MERGE_MSG};
+use crate::object_utils::{object_find, tree_to_entries};
+use crate::rebase::rebase_in_progress;
+use crate::repository::Repository;
+use crate::status::Status;
+
+/// The commit being cherry-picked while a conflicted pick waits to be committed
+const CHERRY_PICK_HEAD: &str = "CHERRY_PICK_HEAD";
+/// The commit being reverted while a conflicted revert waits to be committed
+const REVERT_HEAD: &str = "REVERT_HEAD";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PickAction {
+    // Apply the changes a commit made to its first parent
+    CherryPick,
+    // Apply the inverse of those changes
+    Revert
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PickOutcome {
+    // The change applied cleanly and this commit was made
+    Committed(String),
+    // The pick stopped with these paths conflicted, to be resolved, added and continued
+    Conflicts(Vec<String>)
+}
+
+impl PickAction {
+    fn name(&self) -> &'static str {
+        match self {
+            PickAction::CherryPick => "cherry-pick",
+            PickAction::Revert => "revert"
+        }
+    }
+
+    fn state_file(&self) -> &'static str {
+        match self {
+            PickAction::CherryPick => CHERRY_PICK_HEAD,
+            PickAction::Revert => REVERT_HEAD
+        }
+    }
+}
+
+/// Apply the change a commit made on top of its first parent to HEAD, committing it with the same
+/// message and author. This is a three-way merge of HEAD and the commit with the parent as the base,
+/// so only the lines the commit touched are carried over
+pub fn cherry_pick(repo: &Repository, name: &str) -> Result<PickOutcome, String> {
+    pick_start(repo, name, PickAction::CherryPick)
+}
+
+/// Undo the change a commit made with a new commit, the three-way merge of HEAD and the commit's
+/// first parent with the commit itself as the base
+pub fn revert(repo: &Repository, name: &str) -> Result<PickOutcome, String> {
+    pick_start(repo, name, PickAction::Revert)
+}
+
+fn pick_start(repo: &Repository, name: &str, action: PickAction) -> Result<PickOutcome, String> {
+    if let Some((in_progress, _)) = pick_in_progress(repo) {
+        return Err(format!("A {} is already in progress, continue or abort it first", in_progress.name()));
+    }
+
+    if merge_head_read(repo).is_some() {
+        return Err("You have not concluded your merge (MERGE_HEAD exists), commit or abort it first".to_string());
+    }
+
+    if rebase_in_progress(repo) {
+        return Err("A rebase is in progress, continue or abort it first".to_string());
+    }
+
+    let status = Status::read(repo)?;
+    if !status.staged.is_empty() || !status.unstaged.is_empty() {
+        return Err(format!("Your local changes would be overwritten by {}, commit them first", action.name()));
+    }
+
+    let sha = object_find(repo, name, Some("commit"), true)?;
+    let commit = commit_read(repo, &sha)?;
+
+    let conflicts = pick_apply(repo, &sha, &commit, action)?;
+    let message = pick_message(&sha, &commit, action);
+    pick_state_write(repo, &sha, &message, action)?;
+
+    if !conflicts.is_empty() {
+        return Ok(PickOutcome::Conflicts(conflicts));
+    }
+
+    match repo.commit(message) {
+        Ok(sha) => Ok(PickOutcome::Committed(sha)),
+        Err(e) => {
+            // nothing was changed so there is nothing to resume
+            pick_state_clear(repo)?;
+            Err(format!("The {} of {} is empty: {}", action.name(), &sha[..7], e))
+        }
+    }
+}
+
+/// Merge the change made by a commit, or its inverse, into the index and worktree without committing,
+/// returning the conflicted paths. The caller checks the worktree is clean first
+pub fn pick_apply(repo: &Repository, sha: &str, commit: &GitCommit, action: PickAction) -> Result<Vec<String>, String> {
+    let parents = commit.get_parents();
+    if parents.len() > 1 {
+        return Err(format!("Commit {} is a merge, which cannot be picked as a single change", sha));
+    }
+
+    let commit_tree = tree_to_entries(repo, sha)?;
+    let parent_tree = match parents.first() {
+        Some(parent) => tree_to_entries(repo, parent)?,
+        None => HashMap::new()
+    };
+
+    let label = format!("{} ({})", &sha[..7], commit.summary());
+    let (base, theirs, theirs_label) = match action {
+        PickAction::CherryPick => (parent_tree, commit_tree, label),
+        PickAction::Revert => (commit_tree, parent_tree, format!("parent of {}", label))
+    };
+
+    let head = object_find(repo, "HEAD", Some("commit"), true)?;
+    let ours = tree_to_entries(repo, &head)?;
+
+    let result = merge_trees(repo, &base, &ours, &theirs, "HEAD", &theirs_label)?;
+    merge_apply(repo, &ours, &result)
+}
+
+fn pick_message(sha: &str, commit: &GitCommit, action: PickAction) -> String {
+    match action {
+        PickAction::CherryPick => commit.message(),
+        PickAction::Revert => format!("Revert \"{}\"\n\nThis reverts commit {}.", commit.summary(), sha)
+    }
+}
+
+/// Commit a conflicted cherry-pick or revert once the conflicts are resolved and added
+pub fn pick_continue(repo: &Repository) -> Result<String, String> {
+    if pick_in_progress(repo).is_none() {
+        return Err("There is no cherry-pick or revert in progress".to_string());
+    }
+
+    let message = merge_message_read(repo).unwrap_or_default();
+    repo.commit(message)
+}
+
+/// Throw away a conflicted cherry-pick or revert, putting the index and worktree back to HEAD
+pub fn pick_abort(repo: &Repository) -> Result<(), String> {
+    if pick_in_progress(repo).is_none() {
+        return Err("There is no cherry-pick or revert to abort".to_string());
+    }
+
+    restore_paths(repo, vec![repo.worktree.clone()], true, true, Some("HEAD"))?;
+    pick_state_clear(repo)
+}
+
+/// Which kind of pick is waiting to be committed, and of which commit
+pub fn pick_in_progress(repo: &Repository) -> Option<(PickAction, String)> {
+    [PickAction::CherryPick, PickAction::Revert].into_iter().find_map(|action| {
+        repo_file(repo, vec![action.state_file().to_string()], false).ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| (action, contents.trim().to_string()))
+    })
+}
+
+/// The author a commit made while cherry-picking should keep, that of the picked commit
+pub fn pick_author(repo: &Repository) -> Option<String> {
+    match pick_in_progress(repo)? {
+        (PickAction::CherryPick, sha) => commit_read(repo, &sha).ok()?.author().map(|author| author.to_string()),
+        (PickAction::Revert, _) => None
+    }
+}
+
+fn pick_state_write(repo: &Repository, sha: &str, message: &str, action: PickAction) -> Result<(), String> {
+    repo_file(repo, vec![action.state_file().to_string()], false)
+        .and_then(|path| fs::write(path, format!("{}\n", sha)).map_err(|e| e.to_string()))?;
+    repo_file(repo, vec![MERGE_MSG.to_string()], false)
+        .and_then(|path| fs::write(path, message).map_err(|e| e.to_string()))
+}
+
+/// Remove the pick state files, called once the commit is made or the pick is aborted
+pub fn pick_state_clear(repo: &Repository) -> Result<(), String> {
+    for name in [CHERRY_PICK_HEAD, REVERT_HEAD, MERGE_MSG] {
+        let path = Path::new(&repo_file(repo, vec![name.to_string()], false)?).to_owned();
+        if path.exists() {
+            fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::branch_utils::branch_switch;
+    use crate::checkout::checkout_in_place;
+    use crate::cherry_pick::{cherry_pick, pick_abort, pick_continue, pick_in_progress, revert, PickAction, PickOutcome};
+    use crate::commit_graph::commit_read;
+    use crate::config::UserContents;
+    use crate::file_utils::repo_file;
+    use crate::index::Index;
+    use crate::repository::Repository;
+    use crate::repository::tests::commit_file;
+
+    #[test]
+    fn cherry_pick_and_revert_clean() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let mut repo = Repository::create(tmp_dir_string).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\nthree\n", "Root");
+        branch_switch(&repo, "release", true).unwrap();
+        commit_file(&repo, &tmp_dir, "shared.txt", "ONE\ntwo\nthree\n", "Release only");
+
+        checkout_in_place(&repo, "master").unwrap();
+        repo.conf.contents.user = Some(UserContents { name: "Fixer".to_string(), email: "fixer@example.com".to_string() });
+        let fix = commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\nTHREE\n", "Fix the third line");
+
+        // the fix lands on the release branch without the rest of master, keeping its author
+        checkout_in_place(&repo, "release").unwrap();
+        repo.conf.contents.user = None;
+        let picked = match cherry_pick(&repo, &fix).unwrap() {
+            PickOutcome::Committed(sha) => sha,
+            other => panic!("Expected a commit, got {:?}", other)
+        };
+
+        let commit = commit_read(&repo, &picked).unwrap();
+        assert_eq!(commit.message(), "Fix the third line");
+        assert_eq!(commit.author().unwrap().name, "Fixer");
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "ONE\ntwo\nTHREE\n");
+        assert_eq!(pick_in_progress(&repo), None);
+
+        // picking it again changes nothing so there is nothing to commit
+        assert!(cherry_pick(&repo, &fix).is_err());
+        assert_eq!(pick_in_progress(&repo), None);
+
+        let reverted = match revert(&repo, &picked).unwrap() {
+            PickOutcome::Committed(sha) => sha,
+            other => panic!("Expected a commit, got {:?}", other)
+        };
+        let commit = commit_read(&repo, &reverted).unwrap();
+        assert_eq!(commit.message(), format!("Revert \"Fix the third line\"\n\nThis reverts commit {}.", picked));
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "ONE\ntwo\nthree\n");
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn cherry_pick_unreadable_tree() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\n", "Root");
+        branch_switch(&repo, "feature", true).unwrap();
+        let feature = commit_file(&repo, &tmp_dir, "feature.txt", "feature", "Feature");
+        checkout_in_place(&repo, "master").unwrap();
+        commit_file(&repo, &tmp_dir, "master.txt", "master", "Master");
+
+        // picking a commit whose tree is gone fails, rather than merging in an empty tree which deletes everything
+        let tree = commit_read(&repo, &feature).unwrap().get_tree_string().unwrap();
+        fs::remove_file(repo_file(&repo, vec!["objects".to_string(), tree[..2].to_string(), tree[2..].to_string()], false).unwrap()).unwrap();
+        assert!(cherry_pick(&repo, &feature).is_err());
+        assert!(tmp_dir.path().join("shared.txt").exists() && tmp_dir.path().join("master.txt").exists());
+        assert_eq!(pick_in_progress(&repo), None);
+        assert_eq!(Index::read(&repo).unwrap().entries.len(), 2);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn cherry_pick_conflicts_continue_and_abort() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\n", "Root");
+        branch_switch(&repo, "release", true).unwrap();
+        let release = commit_file(&repo, &tmp_dir, "shared.txt", "release\n", "Release change");
+        checkout_in_place(&repo, "master").unwrap();
+        let fix = commit_file(&repo, &tmp_dir, "shared.txt", "fixed\n", "Fix");
+        checkout_in_place(&repo, "release").unwrap();
+
+        assert_eq!(cherry_pick(&repo, &fix), Ok(PickOutcome::Conflicts(vec!["shared.txt".to_string()])));
+        assert_eq!(pick_in_progress(&repo), Some((PickAction::CherryPick, fix.clone())));
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(),
+                   format!("<<<<<<< HEAD\nrelease\n=======\nfixed\n>>>>>>> {} (Fix)\n", &fix[..7]));
+        assert!(cherry_pick(&repo, &fix).is_err());
+        assert!(pick_continue(&repo).is_err());
+
+        pick_abort(&repo).unwrap();
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "release\n");
+        assert!(Index::read(&repo).unwrap().conflicted_paths().is_empty());
+        assert_eq!(pick_in_progress(&repo), None);
+
+        cherry_pick(&repo, &fix).unwrap();
+        fs::write(tmp_dir.path().join("shared.txt"), "release fixed\n").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let picked = pick_continue(&repo).unwrap();
+
+        let commit = commit_read(&repo, &picked).unwrap();
+        assert_eq!(commit.get_parents(), vec![release]);
+        assert_eq!(commit.message(), "Fix");
+        assert_eq!(pick_in_progress(&repo), None);
+
+        tmp_dir.close().unwrap();
+    }
+}
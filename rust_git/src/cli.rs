@@ -1,14 +1,26 @@
-use std::fs::{canonicalize, create_dir_all};
+use std::fs::{self, canonicalize, create_dir_all};
 use std::path::Path;
 use clap::{Parser, Subcommand};
-use crate::branch_utils::branch_get_active;
+use crate::branch_utils::{branch_create, branch_delete, branch_get_active, branch_list, branch_rename, branch_switch};
 use crate::git_object::GitObject::Commit;
-use crate::git_object::{GitObject, GitTag};
+use crate::checkout::{checkout_in_place, restore_paths};
+use crate::cherry_pick::{cherry_pick, pick_abort, pick_continue, revert, PickAction, PickOutcome};
+use crate::commit_graph::{is_ancestor, merge_bases};
+use crate::config::ConfigFormat;
+use crate::diff::{diff_targets, DiffTarget};
+use crate::git_object::GitTag;
+use crate::graphviz::GraphvizExport;
 use crate::ignore::Ignore;
 use crate::index::Index;
-use crate::object_utils::{object_find, object_read, object_write, tree_to_dict};
+use crate::log::{log_revisions, parse_date, LogOptions, LogWalk};
+use crate::merge::{merge, merge_abort, merge_message_read, MergeOutcome};
+use crate::object_utils::{object_find, object_read};
+use crate::rebase::{rebase, rebase_abort, rebase_continue, rebase_todo_parse, RebaseOutcome};
 use crate::refs::Ref;
+use crate::reset::{reset, reset_paths, ResetMode};
 use crate::repository::Repository;
+use crate::revision::RevisionRange;
+use crate::status::Status;
 
 #[derive(Parser)]
 #[command(about, long_about = None)]
@@ -21,6 +33,8 @@ struct Args {
 #[derive(Debug)]
 enum Commands {
     Init {
+        #[arg(long, help="Write the config, refs, index and objects in the formats git uses, so git can read the repo")]
+        git_compatible: bool,
         #[arg(help="The path where the repo will be initiated")]
         path: String
     },
@@ -40,10 +54,22 @@ enum Commands {
         write: bool
     },
     Checkout {
-        #[arg(help="The commit or tree to checkout")]
-        commit: String,
-        #[arg(help="The EMPTY directory to checkout to")]
-        path: String
+        #[arg(help="The branch or commit to checkout")]
+        commit: Option<String>,
+        #[arg(help="An EMPTY directory to extract the commit into, if not given the current worktree is updated")]
+        path: Option<String>,
+        #[arg(last = true, help="Restore just these files, from the commit if given or else from the index")]
+        paths: Vec<String>
+    },
+    Restore {
+        #[arg(long, help="Reset the index entries of the paths, by default from HEAD")]
+        staged: bool,
+        #[arg(long, help="Restore the worktree files, the default unless --staged is given")]
+        worktree: bool,
+        #[arg(short = 's', long, help="The revision to restore from instead of the index or HEAD")]
+        source: Option<String>,
+        #[arg(required = true, help="The paths to restore")]
+        paths: Vec<String>
     },
     CheckIgnore {
         #[arg(help="Paths to check. Return paths that will be ignored")]
@@ -52,14 +78,147 @@ enum Commands {
     Tag {
         #[arg(short = 'a', help="If set we create a tag object")]
         store_true: bool,
-        #[arg(help="The new tags name")]
-        name: String,
+        #[arg(short = 'm', long, help="The message of an annotated tag, implies -a")]
+        message: Option<String>,
+        #[arg(short = 'd', long, help="Delete the tag")]
+        delete: bool,
+        #[arg(short = 'f', long, help="Replace an existing tag of the same name")]
+        force: bool,
+        #[arg(help="The new tags name, list all tags if not given")]
+        name: Option<String>,
         #[arg(help="The object the new tag will point to", default_value = "HEAD")]
         object: String
     },
+    Add {
+        #[arg(required = true, help="Files to stage, directories are added recursively")]
+        paths: Vec<String>
+    },
+    Rm {
+        #[arg(long, help="Only remove the paths from the index, keeping the files in the worktree")]
+        cached: bool,
+        #[arg(short = 'f', long, help="Remove files even if they have staged or local modifications")]
+        force: bool,
+        #[arg(long, help="Dont fail if a path does not match any staged files")]
+        ignore_unmatch: bool,
+        #[arg(required = true, help="Files to remove, directories remove everything staged beneath them")]
+        paths: Vec<String>
+    },
+    Commit {
+        #[arg(short = 'm', long, help="The commit message, when finishing a merge it defaults to the prepared merge message")]
+        message: Option<String>
+    },
+    Merge {
+        #[arg(long, help="Abandon a conflicted merge, resetting the index and worktree to HEAD")]
+        abort: bool,
+        #[arg(required_unless_present = "abort", help="The branch or commit to merge into HEAD")]
+        name: Option<String>
+    },
+    CherryPick {
+        #[arg(long = "continue", conflicts_with = "abort", help="Commit the pick once its conflicts are resolved and added")]
+        resume: bool,
+        #[arg(long, help="Abandon a conflicted pick, resetting the index and worktree to HEAD")]
+        abort: bool,
+        #[arg(required_unless_present_any = ["resume", "abort"], help="The commit whose change to apply to HEAD")]
+        name: Option<String>
+    },
+    Revert {
+        #[arg(long = "continue", conflicts_with = "abort", help="Commit the revert once its conflicts are resolved and added")]
+        resume: bool,
+        #[arg(long, help="Abandon a conflicted revert, resetting the index and worktree to HEAD")]
+        abort: bool,
+        #[arg(required_unless_present_any = ["resume", "abort"], help="The commit whose change to undo")]
+        name: Option<String>
+    },
+    Reset {
+        #[arg(long, group = "mode", help="Only move the branch, keeping the index and worktree")]
+        soft: bool,
+        #[arg(long, group = "mode", help="Move the branch and reset the index but not the worktree, the default")]
+        mixed: bool,
+        #[arg(long, group = "mode", help="Move the branch and reset both the index and the worktree, losing local changes")]
+        hard: bool,
+        #[arg(default_value = "HEAD", help="The commit to move to, or to reset the paths from")]
+        revision: String,
+        #[arg(last = true, help="Unstage just these paths instead of moving the branch")]
+        paths: Vec<String>
+    },
+    Rebase {
+        #[arg(long = "continue", conflicts_with = "abort", help="Finish the stopped step once its conflicts are resolved and added, then carry on")]
+        resume: bool,
+        #[arg(long, help="Abandon the rebase, putting the branch back where it was")]
+        abort: bool,
+        #[arg(long, help="A file of 'pick', 'reword', 'squash', 'fixup' or 'drop' lines to run instead of picking every commit")]
+        todo: Option<String>,
+        #[arg(required_unless_present_any = ["resume", "abort"], help="The branch or commit to replay HEAD onto")]
+        upstream: Option<String>
+    },
+    MergeBase {
+        #[arg(long, help="Print true if the first commit is an ancestor of the second, else false, instead of printing the base")]
+        is_ancestor: bool,
+        #[arg(long, help="Print every best common ancestor rather than just one")]
+        all: bool,
+        #[arg(num_args = 2, required = true, help="The two commits")]
+        commits: Vec<String>
+    },
+    Branch {
+        #[arg(short = 'd', long, help="Delete the branches, if they are merged into HEAD")]
+        delete: bool,
+        #[arg(short = 'D', help="Delete the branches even if they are not merged")]
+        force_delete: bool,
+        #[arg(short = 'm', long = "move", help="Rename a branch: [old name] <new name>")]
+        rename: bool,
+        #[arg(short = 'f', long, help="Move or replace an existing branch")]
+        force: bool,
+        #[arg(help="<name> [start point] to create a branch, list branches if not given")]
+        names: Vec<String>
+    },
+    Switch {
+        #[arg(short = 'c', long, help="Create the branch at HEAD before switching to it")]
+        create: bool,
+        #[arg(help="The branch to move HEAD to")]
+        branch: String
+    },
+    RevParse {
+        #[arg(required = true, help="Revisions to resolve e.g. HEAD~2, v1.0^{tree}, main:src/lib.rs or a..b")]
+        revisions: Vec<String>
+    },
+    Diff {
+        #[arg(long, help="Compare the index to HEAD, or to the given revision")]
+        cached: bool,
+        #[arg(short = 'U', long = "unified", default_value_t = 3, help="Lines of context around each change")]
+        context: usize,
+        #[arg(help="No revisions compares the worktree to the index, one compares a revision to the worktree and two (or a..b) compare the revisions")]
+        revisions: Vec<String>
+    },
+    Log {
+        #[arg(long, help="Show each commit as 'short sha summary' on a single line")]
+        oneline: bool,
+        #[arg(short = 'n', long = "max-count", help="Show at most this many commits")]
+        max_count: Option<usize>,
+        #[arg(long, help="Draw the history as an ascii graph down the left")]
+        graph: bool,
+        #[arg(long, help="Only show commits whose author matches this regex")]
+        author: Option<String>,
+        #[arg(long, help="Only show commits made at or after this time, a timestamp or YYYY-MM-DD [HH:MM:SS]")]
+        since: Option<String>,
+        #[arg(long, help="Only show commits made at or before this time, a timestamp or YYYY-MM-DD [HH:MM:SS]")]
+        until: Option<String>,
+        #[arg(help="The revisions to walk from, a..b, a...b and ^a exclude history", default_value = "HEAD")]
+        revisions: Vec<String>,
+        #[arg(last = true, help="Only show commits which change these paths")]
+        paths: Vec<String>
+    },
+    Graphviz {
+        #[arg(long, help="Include the trees and blobs of each commit")]
+        objects: bool,
+        #[arg(help="The revisions to walk from, a..b, a...b and ^a exclude history", default_value = "HEAD")]
+        revisions: Vec<String>
+    },
     LsFiles,
     ShowRef,
-    Status
+    Status {
+        #[arg(long, help="Print the machine readable 'XY path' format")]
+        porcelain: bool
+    }
 }
 
 pub struct Cli {
@@ -82,15 +241,55 @@ impl Cli {
 
     fn process_command(&self, command: &Commands) {
         let result = match command {
-            Commands::Init { path } => self.process_init(path),
+            Commands::Init { git_compatible, path } => self.process_init(*git_compatible, path),
             Commands::CatFile { object_type, object_name } => self.process_cat_file(object_type, object_name),
             Commands::HashObject { object_type, object_path, write } => self.process_hash_object(object_type, object_path, write),
-            Commands::Checkout { commit, path } => self.process_checkout(commit, path),
+            Commands::Checkout { commit, path: None, paths } if !paths.is_empty() => {
+                // checkout -- <paths> restores from the index, checkout <rev> -- <paths> from a commit
+                let from_commit = commit.is_some();
+                self.process_restore(from_commit, true, commit, paths)
+            },
+            Commands::Checkout { path: Some(_), paths, .. } if !paths.is_empty() => {
+                Err("Paths cannot be restored when extracting a commit into a directory".to_string())
+            },
+            Commands::Checkout { commit: Some(commit), path: Some(path), .. } => self.process_checkout(commit, path),
+            Commands::Checkout { commit: Some(commit), path: None, .. } => self.process_checkout_in_place(commit),
+            Commands::Checkout { .. } => Err("Checkout needs a commit or paths to restore".to_string()),
+            Commands::Restore { staged, worktree, source, paths } => {
+                self.process_restore(*staged, *worktree || !*staged, source, paths)
+            },
             Commands::ShowRef => self.process_show_ref(),
             Commands::CheckIgnore { paths } => self.process_check_ignore(paths),
-            Commands::Tag { store_true, name, object } => self.process_tag(store_true, name, object),
+            Commands::Tag { store_true, message, delete, force, name, object } => {
+                self.process_tag(*store_true, message, *delete, *force, name, object)
+            },
+            Commands::Add { paths } => self.process_add(paths),
+            Commands::Rm { cached, force, ignore_unmatch, paths } => self.process_rm(*cached, *force, *ignore_unmatch, paths),
+            Commands::Commit { message } => self.process_commit(message.as_deref()),
+            Commands::Merge { abort, name } => self.process_merge(*abort, name.as_deref()),
+            Commands::CherryPick { resume, abort, name } => self.process_pick(PickAction::CherryPick, *resume, *abort, name.as_deref()),
+            Commands::Revert { resume, abort, name } => self.process_pick(PickAction::Revert, *resume, *abort, name.as_deref()),
+            Commands::Reset { soft, mixed: _, hard, revision, paths } => self.process_reset(*soft, *hard, revision, paths),
+            Commands::Rebase { resume, abort, todo, upstream } => self.process_rebase(*resume, *abort, todo.as_deref(), upstream.as_deref()),
+            Commands::MergeBase { is_ancestor, all, commits } => self.process_merge_base(*is_ancestor, *all, commits),
+            Commands::Branch { delete, force_delete, rename, force, names } => {
+                self.process_branch(*delete, *force_delete, *rename, *force, names)
+            },
+            Commands::Switch { create, branch } => self.process_switch(*create, branch),
+            Commands::RevParse { revisions } => self.process_rev_parse(revisions),
+            Commands::Diff { cached, context, revisions } => self.process_diff(*cached, *context, revisions),
+            Commands::Log { oneline, max_count, graph, author, since, until, revisions, paths } => {
+                match (since.as_deref().map(parse_date).transpose(), until.as_deref().map(parse_date).transpose()) {
+                    (Ok(since), Ok(until)) => {
+                        let options = LogOptions { max_count: *max_count, author: author.clone(), since, until, paths: paths.clone() };
+                        self.process_log(options, *oneline, *graph, revisions)
+                    },
+                    (Err(e), _) | (_, Err(e)) => Err(e)
+                }
+            },
+            Commands::Graphviz { objects, revisions } => self.process_graphviz(*objects, revisions),
             Commands::LsFiles => self.process_ls_files(),
-            Commands::Status => self.process_status()
+            Commands::Status { porcelain } => self.process_status(*porcelain)
         };
 
         match result {
@@ -99,16 +298,17 @@ impl Cli {
         }
     }
 
-    fn process_init(&self, path: &String) -> Result<(), String> {
-        println!("Running init on: {}", path.clone());
-        Repository::create(path.clone()).and_then(|_| Ok(()))
+    fn process_init(&self, git_compatible: bool, path: &str) -> Result<(), String> {
+        println!("Running init on: {}", path);
+        let format = if git_compatible { ConfigFormat::Ini } else { ConfigFormat::Json };
+        Repository::create_with_format(path.to_string(), format).map(|_| ())
     }
 
-    fn process_cat_file(&self, object_type: &String, object_name: &String) -> Result<(), String> {
-        println!("Running cat file on {} {}", object_type.clone(), object_name.clone());
+    fn process_cat_file(&self, object_type: &str, object_name: &str) -> Result<(), String> {
+        println!("Running cat file on {} {}", object_type, object_name);
 
         let repo = Repository::find(String::from("."), true)?;
-        let obj_name = object_find(&repo, &object_name, &object_type, true);
+        let obj_name = object_find(&repo, object_name, Some(object_type), true)?;
         let obj = object_read(&repo, obj_name)?;
 
         println!("{:?}", obj);
@@ -119,7 +319,7 @@ impl Cli {
         todo!("Hash object cli not implemented yet, called for {} {} {}", object_type, object_path, write);
     }
 
-    pub fn process_checkout(&self, commit: &String, path: &String) -> Result<(), String> {
+    pub fn process_checkout(&self, commit: &str, path: &str) -> Result<(), String> {
         let path_obj = Path::new(path);
 
         if path_obj.exists() {
@@ -137,7 +337,7 @@ impl Cli {
         let repo = Repository::find(String::from("."), true)?;
 
         // TODO: technically this should support directly checking out a tree too but...
-        let commit_obj_name = object_find(&repo, &commit, &"commit".to_string(), true);
+        let commit_obj_name = object_find(&repo, commit, Some("commit"), true)?;
         let commit_obj = match object_read(&repo, commit_obj_name)? {
             Commit(obj) => obj,
             _ => {
@@ -151,6 +351,16 @@ impl Cli {
         Ok(())
     }
 
+    fn process_checkout_in_place(&self, target: &str) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        checkout_in_place(&repo, target)
+    }
+
+    fn process_restore(&self, staged: bool, worktree: bool, source: &Option<String>, paths: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        restore_paths(&repo, paths.to_vec(), staged, worktree, source.as_deref())
+    }
+
     fn process_show_ref(&self) -> Result<(), String> {
         let repo = Repository::find(String::from("."), true)?;
         let refs = Ref::all_refs(&repo);
@@ -178,71 +388,314 @@ impl Cli {
         Ok(())
     }
 
-    fn process_tag(&self, store_true: &bool, name: &String, object: &String) -> Result<(), String> {
+    fn process_tag(&self, store_true: bool, message: &Option<String>, delete: bool, force: bool, name: &Option<String>, object: &str) -> Result<(), String> {
         let repo = Repository::find(String::from("."), true)?;
 
-        let tag = if *store_true {
-            GitTag::new_object(name.clone(), object.clone(), &repo)
-        } else {
-            GitTag::new_lightweight(name.clone(), object.clone(), &repo)
+        let name = match name {
+            Some(name) => name.clone(),
+            None => {
+                for tag in GitTag::list(&repo) {
+                    println!("{}", tag);
+                }
+                return Ok(());
+            }
+        };
+
+        if delete {
+            return GitTag::delete(&repo, &name).map(|_| println!("Deleted tag '{}'", name));
+        }
+
+        let tag = match message {
+            Some(message) => GitTag::new_object(name, object.to_string(), message.clone(), &repo)?,
+            None if store_true => return Err("An annotated tag needs a message, pass one with -m".to_string()),
+            None => GitTag::new_lightweight(name, object.to_string(), &repo)?
         };
 
-        object_write(GitObject::Tag(tag), Some(&repo)).and_then(|sha| {
-            println!("Created tag with hash: {}", sha);
-            Ok(())
-        })
+        tag.write(&repo, force)
     }
 
-    fn process_ls_files(&self) -> Result<(), String> {
+    fn process_add(&self, paths: &[String]) -> Result<(), String> {
         let repo = Repository::find(String::from("."), true)?;
-        // TODO: would be nice to use '?' op here but struggling to convert Err String to Err &'static str
-        //       do I need a pass over whole codebase to unify errors to be String instead? could be much nicer
-        //       that way I can use e.into() or format!("{}", e) to convert err to String in many cases much more nicely
-        let index = Index::read(&repo)?;
+        repo.add(paths.to_vec())
+    }
 
-        for entry in index.entries {
-            println!("{:?}", entry);
+    fn process_rm(&self, cached: bool, force: bool, ignore_unmatch: bool, paths: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        repo.rm(paths.to_vec(), !cached, ignore_unmatch, force)
+    }
+
+    fn process_commit(&self, message: Option<&str>) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        let message = match message {
+            Some(message) => message.to_string(),
+            None => merge_message_read(&repo).ok_or("Aborting commit due to empty commit message, pass one with -m")?
+        };
+
+        repo.commit(message).map(|_| ())
+    }
+
+    fn process_merge(&self, abort: bool, name: Option<&str>) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        if abort {
+            return merge_abort(&repo);
+        }
+
+        match merge(&repo, name.unwrap_or_default())? {
+            MergeOutcome::UpToDate => println!("Already up to date."),
+            MergeOutcome::FastForward(sha) => println!("Fast-forward to {}", sha),
+            MergeOutcome::Merged(_) => println!("Merge made by the 'three-way' strategy."),
+            MergeOutcome::Conflicts(_) => println!("Automatic merge failed; fix conflicts and then commit the result.")
+        }
+
+        Ok(())
+    }
+
+    fn process_pick(&self, action: PickAction, resume: bool, abort: bool, name: Option<&str>) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        if abort {
+            return pick_abort(&repo);
+        }
+
+        if resume {
+            return pick_continue(&repo).map(|_| ());
+        }
+
+        let name = name.unwrap_or_default();
+        let outcome = match action {
+            PickAction::CherryPick => cherry_pick(&repo, name)?,
+            PickAction::Revert => revert(&repo, name)?
+        };
+
+        if let PickOutcome::Conflicts(_) = outcome {
+            println!("Could not apply {}; fix conflicts, add them and then continue.", name);
+        }
+
+        Ok(())
+    }
+
+    fn process_reset(&self, soft: bool, hard: bool, revision: &str, paths: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        if !paths.is_empty() {
+            if soft || hard {
+                return Err("Cannot do a soft or hard reset with paths".to_string());
+            }
+            return reset_paths(&repo, revision, paths);
+        }
+
+        let mode = match (soft, hard) {
+            (true, _) => ResetMode::Soft,
+            (_, true) => ResetMode::Hard,
+            _ => ResetMode::Mixed
+        };
+
+        let sha = reset(&repo, revision, mode)?;
+        if mode == ResetMode::Hard {
+            println!("HEAD is now at {}", &sha[..7]);
         }
 
         Ok(())
     }
 
-    fn process_status(&self) -> Result<(), String> {
+    fn process_rebase(&self, resume: bool, abort: bool, todo: Option<&str>, upstream: Option<&str>) -> Result<(), String> {
         let repo = Repository::find(String::from("."), true)?;
 
-        if let Some(branch) = branch_get_active(&repo) {
-            println!("Active branch: {}", branch);
+        if abort {
+            return rebase_abort(&repo);
+        }
+
+        let outcome = if resume {
+            rebase_continue(&repo)?
         } else {
-            println!("HEAD detached at {}", object_find(&repo, &"HEAD".to_string(), &"".to_string(), true));
+            let steps = match todo {
+                Some(path) => Some(rebase_todo_parse(&repo, &fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?)?),
+                None => None
+            };
+            rebase(&repo, upstream.unwrap_or_default(), steps)?
+        };
+
+        match outcome {
+            RebaseOutcome::UpToDate => println!("Current branch is up to date."),
+            RebaseOutcome::Finished(sha) => println!("Successfully rebased, HEAD is now at {}", sha),
+            RebaseOutcome::Conflicts(_) => println!("Could not apply a commit; fix conflicts, add them and then run rebase --continue.")
         }
 
-        self.print_status_head_index(&repo)?;
+        Ok(())
+    }
+
+    fn process_merge_base(&self, check_ancestor: bool, all: bool, commits: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        let a = object_find(&repo, &commits[0], Some("commit"), true)?;
+        let b = object_find(&repo, &commits[1], Some("commit"), true)?;
+
+        // a 'no' is an answer rather than a failure, so it is printed and not returned as an error
+        if check_ancestor {
+            println!("{}", is_ancestor(&repo, &a, &b)?);
+            return Ok(());
+        }
+
+        let bases = merge_bases(&repo, &a, &b)?;
+        if bases.is_empty() {
+            return Err(format!("{} and {} have no common ancestor", commits[0], commits[1]));
+        }
+
+        let shown = if all { bases.len() } else { 1 };
+        for base in &bases[..shown] {
+            println!("{}", base);
+        }
 
         Ok(())
     }
 
-    fn print_status_head_index(&self, repo: &Repository) -> Result<(), String> {
-        let index = Index::read(&repo)?;
-        let mut head = tree_to_dict(&repo, &"HEAD".to_string(), None);
+    fn process_branch(&self, delete: bool, force_delete: bool, rename: bool, force: bool, names: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
 
-        for entry in index.entries {
-            if head.contains_key(&entry.name) {
-                if head.get(&entry.name).unwrap() != &entry.sha {
-                    println!("\tmodified: {}", entry.name);
+        if delete || force_delete {
+            for name in names {
+                branch_delete(&repo, name, force_delete)?;
+            }
+            return Ok(());
+        }
+
+        if rename {
+            return match names {
+                [new_name] => {
+                    let current = branch_get_active(&repo).ok_or("HEAD is detached, no branch to rename".to_string())?;
+                    branch_rename(&repo, &current, new_name, force)
+                },
+                [old_name, new_name] => branch_rename(&repo, old_name, new_name, force),
+                _ => Err("Rename takes [old name] <new name>".to_string())
+            };
+        }
+
+        match names {
+            [] => {
+                let active = branch_get_active(&repo);
+                for branch in branch_list(&repo) {
+                    let marker = if active.as_ref() == Some(&branch) { "*" } else { " " };
+                    println!("{} {}", marker, branch);
                 }
+                Ok(())
+            },
+            [name] => branch_create(&repo, name, "HEAD", force).map(|_| ()),
+            [name, start_point] => branch_create(&repo, name, start_point, force).map(|_| ()),
+            _ => Err("Branch takes <name> [start point]".to_string())
+        }
+    }
+
+    fn process_switch(&self, create: bool, branch: &str) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        // A new branch starts at HEAD so only HEAD needs to move, otherwise update the worktree too
+        if create {
+            return branch_switch(&repo, branch, true).map(|_| println!("Switched to a new branch '{}'", branch));
+        }
 
-                head.remove(&entry.name);
-            } else {
-                println!("\tadded: {}", entry.name);
+        if !branch_list(&repo).contains(&branch.to_string()) {
+            return Err(format!("No branch named '{}'", branch));
+        }
+
+        checkout_in_place(&repo, branch)
+    }
+
+    fn process_rev_parse(&self, revisions: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        // Ranges are printed the way git does, with the excluded side prefixed by ^
+        for revision in revisions {
+            match RevisionRange::parse(revision) {
+                RevisionRange::Single(name) => println!("{}", object_find(&repo, &name, None, true)?),
+                RevisionRange::TwoDot { exclude, include } => {
+                    println!("{}", object_find(&repo, &include, None, true)?);
+                    println!("^{}", object_find(&repo, &exclude, None, true)?);
+                },
+                RevisionRange::ThreeDot { left, right } => {
+                    println!("{}", object_find(&repo, &left, None, true)?);
+                    println!("{}", object_find(&repo, &right, None, true)?);
+                }
             }
         }
 
-        for entry in head.keys() {
-            println!("\tdeleted: {}", entry);
+        Ok(())
+    }
+
+    fn process_diff(&self, cached: bool, context: usize, revisions: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        let (old, new) = match (revisions, cached) {
+            ([], false) => (DiffTarget::index(&repo)?, DiffTarget::worktree(&repo)?),
+            ([], true) => (DiffTarget::tree(&repo, "HEAD")?, DiffTarget::index(&repo)?),
+            ([revision], _) => match RevisionRange::parse(revision) {
+                RevisionRange::Single(name) if cached => (DiffTarget::tree(&repo, &name)?, DiffTarget::index(&repo)?),
+                RevisionRange::Single(name) => (DiffTarget::tree(&repo, &name)?, DiffTarget::worktree(&repo)?),
+                RevisionRange::TwoDot { exclude, include } => (DiffTarget::tree(&repo, &exclude)?, DiffTarget::tree(&repo, &include)?),
+                RevisionRange::ThreeDot { .. } => return Err("Diff does not support a...b ranges".to_string())
+            },
+            ([old, new], false) => (DiffTarget::tree(&repo, old)?, DiffTarget::tree(&repo, new)?),
+            _ => return Err("Diff takes at most two revisions, or one with --cached".to_string())
+        };
+
+        print!("{}", diff_targets(&repo, &old, &new, context)?);
+
+        Ok(())
+    }
+
+    fn process_log(&self, mut options: LogOptions, oneline: bool, graph: bool, revisions: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+
+        options.paths = options.paths.iter()
+            .map(|path| repo.worktree_relative_path(path))
+            .collect::<Result<Vec<String>, String>>()?;
+
+        let (include, exclude) = log_revisions(&repo, revisions)?;
+        let walk = LogWalk::new(&repo, &include, &exclude)?.filter(&repo, &options)?;
+
+        for line in walk.format(oneline, graph) {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+
+    fn process_graphviz(&self, objects: bool, revisions: &[String]) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        print!("{}", GraphvizExport::default().export(&repo, revisions, objects)?);
+
+        Ok(())
+    }
+
+    fn process_ls_files(&self) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        // TODO: would be nice to use '?' op here but struggling to convert Err String to Err &'static str
+        //       do I need a pass over whole codebase to unify errors to be String instead? could be much nicer
+        //       that way I can use e.into() or format!("{}", e) to convert err to String in many cases much more nicely
+        let index = Index::read(&repo)?;
+
+        for entry in index.entries {
+            println!("{:?}", entry);
         }
 
         Ok(())
+    }
+
+    fn process_status(&self, porcelain: bool) -> Result<(), String> {
+        let repo = Repository::find(String::from("."), true)?;
+        let status = Status::read(&repo)?;
+
+        let lines = if porcelain {
+            status.porcelain_lines()
+        } else {
+            status.long_lines()
+        };
 
+        for line in lines {
+            println!("{}", line);
+        }
+
+        Ok(())
     }
 
 }
\ No newline at end of file
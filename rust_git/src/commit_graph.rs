@@ -0,0 +1,302 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::git_object::{GitCommit, GitObject};
+use crate::object_utils::object_read;
+use crate::repository::Repository;
+
+// Flags painted onto commits while searching for merge bases, as in git's paint_down_to_common
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 2;
+const STALE: u8 = 4;
+
+/// The order commits come out of a walk in. Both always show a commit before its parents
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CommitOrder {
+    // The most recently committed of the commits whose children have all been shown, as git log --date-order
+    Date,
+    // Follow one line of history as far as possible before the next, as git log --topo-order
+    Topo
+}
+
+pub fn commit_read(repo: &Repository, sha: &str) -> Result<GitCommit, String> {
+    match object_read(repo, sha.to_string())? {
+        GitObject::Commit(commit) => Ok(commit),
+        other => Err(format!("Expected {} to be a commit but found a {}", sha, other.format_name()))
+    }
+}
+
+/// When the commit was committed, falling back to the author time
+pub fn commit_time(commit: &GitCommit) -> i64 {
+    commit.committer().or(commit.author()).map(|sig| sig.timestamp).unwrap_or(0)
+}
+
+/// Every commit reachable from the tips, including the tips themselves
+pub fn commit_ancestors(repo: &Repository, tips: &[String]) -> Result<HashSet<String>, String> {
+    let mut seen = HashSet::new();
+    let mut stack = tips.to_vec();
+
+    while let Some(sha) = stack.pop() {
+        if seen.insert(sha.clone()) {
+            stack.extend(commit_read(repo, &sha)?.get_parents());
+        }
+    }
+
+    Ok(seen)
+}
+
+/// True if ancestor can be reached from descendant by following parents, a commit counts as its own ancestor
+pub fn is_ancestor(repo: &Repository, ancestor: &str, descendant: &str) -> Result<bool, String> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![descendant.to_string()];
+
+    while let Some(sha) = stack.pop() {
+        if sha == ancestor {
+            return Ok(true);
+        }
+
+        if seen.insert(sha.clone()) {
+            stack.extend(commit_read(repo, &sha)?.get_parents());
+        }
+    }
+
+    Ok(false)
+}
+
+/// How many commits a has that b does not, and how many b has that a does not
+pub fn ahead_behind(repo: &Repository, a: &str, b: &str) -> Result<(usize, usize), String> {
+    let a_ancestors = commit_ancestors(repo, &[a.to_string()])?;
+    let b_ancestors = commit_ancestors(repo, &[b.to_string()])?;
+
+    Ok((a_ancestors.difference(&b_ancestors).count(), b_ancestors.difference(&a_ancestors).count()))
+}
+
+/// The best common ancestor of two commits, see merge_bases. Where there are several (a criss-cross
+/// merge) the most recently committed is used
+pub fn merge_base(repo: &Repository, a: &str, b: &str) -> Result<Option<String>, String> {
+    Ok(merge_bases(repo, a, b)?.into_iter().next())
+}
+
+/// All the best common ancestors of two commits: commits reachable from both which are not an ancestor
+/// of another such commit, most recent first.
+///
+/// Like git we walk back from both commits newest first, painting each commit with the side(s) it was
+/// reached from. A commit reached from both sides is a candidate, and everything below it is marked stale
+/// so the walk can stop once only stale commits are left
+pub fn merge_bases(repo: &Repository, a: &str, b: &str) -> Result<Vec<String>, String> {
+    if a == b {
+        return Ok(vec![a.to_string()]);
+    }
+
+    let mut commits: HashMap<String, GitCommit> = HashMap::new();
+    let mut flags: HashMap<String, u8> = HashMap::new();
+    let mut queue: BinaryHeap<(i64, String)> = BinaryHeap::new();
+    let mut candidates: Vec<(i64, String)> = Vec::new();
+
+    for (sha, flag) in [(a, PARENT1), (b, PARENT2)] {
+        let commit = commit_read(repo, sha)?;
+        queue.push((commit_time(&commit), sha.to_string()));
+        commits.insert(sha.to_string(), commit);
+        flags.insert(sha.to_string(), flag);
+    }
+
+    while queue.iter().any(|(_, sha)| flags[sha] & STALE == 0) {
+        let (time, sha) = queue.pop().unwrap();
+        let mut flag = flags[&sha] & (PARENT1 | PARENT2 | STALE);
+
+        if flag == PARENT1 | PARENT2 {
+            if !candidates.iter().any(|(_, candidate)| *candidate == sha) {
+                candidates.push((time, sha.clone()));
+            }
+            flag |= STALE;
+        }
+
+        for parent in commits[&sha].get_parents() {
+            let parent_flag = flags.get(&parent).copied().unwrap_or(0);
+            if parent_flag & flag == flag {
+                continue;
+            }
+
+            if !commits.contains_key(&parent) {
+                commits.insert(parent.clone(), commit_read(repo, &parent)?);
+            }
+
+            flags.insert(parent.clone(), parent_flag | flag);
+            queue.push((commit_time(&commits[&parent]), parent));
+        }
+    }
+
+    // a candidate found before the walk reached a newer one below it can still be redundant
+    let mut bases = Vec::new();
+    for (time, sha) in &candidates {
+        let mut redundant = false;
+        for (_, other) in candidates.iter().filter(|(_, other)| other != sha) {
+            if is_ancestor(repo, sha, other)? {
+                redundant = true;
+                break;
+            }
+        }
+
+        if !redundant {
+            bases.push((*time, sha.clone()));
+        }
+    }
+
+    bases.sort_by(|a, b| b.cmp(a));
+    Ok(bases.into_iter().map(|(_, sha)| sha).collect())
+}
+
+/// The commits reachable from the include tips but not from the exclude tips, in the given order
+pub fn commit_walk(repo: &Repository, include: &[String], exclude: &[String], order: CommitOrder) -> Result<Vec<(String, GitCommit)>, String> {
+    let hidden = commit_ancestors(repo, exclude)?;
+
+    let mut found: HashMap<String, GitCommit> = HashMap::new();
+    let mut stack: Vec<String> = include.iter().filter(|sha| !hidden.contains(*sha)).cloned().collect();
+
+    while let Some(sha) = stack.pop() {
+        if found.contains_key(&sha) {
+            continue;
+        }
+
+        let commit = commit_read(repo, &sha)?;
+        stack.extend(commit.get_parents().into_iter().filter(|parent| !hidden.contains(parent)));
+        found.insert(sha, commit);
+    }
+
+    // a commit is ready to be shown once all of its children have been
+    let mut children: HashMap<String, usize> = HashMap::new();
+    for commit in found.values() {
+        for parent in commit.get_parents() {
+            *children.entry(parent).or_insert(0) += 1;
+        }
+    }
+
+    let mut tips: Vec<(i64, String)> = found.iter()
+        .filter(|(sha, _)| !children.contains_key(*sha))
+        .map(|(sha, commit)| (commit_time(commit), sha.clone()))
+        .collect();
+    tips.sort();
+
+    // for date order the ready commits form a heap, for topo order a stack so that the parents of the
+    // commit just shown come next. Pushing the parents in reverse keeps a merge's first parent on top
+    let mut heap: BinaryHeap<(i64, String)> = BinaryHeap::new();
+    let mut ready: Vec<String> = Vec::new();
+    match order {
+        CommitOrder::Date => heap.extend(tips),
+        CommitOrder::Topo => ready.extend(tips.into_iter().map(|(_, sha)| sha))
+    }
+
+    let mut commits = Vec::new();
+    loop {
+        let sha = match order {
+            CommitOrder::Date => heap.pop().map(|(_, sha)| sha),
+            CommitOrder::Topo => ready.pop()
+        };
+
+        let sha = match sha {
+            Some(sha) => sha,
+            None => break
+        };
+
+        let commit = found.remove(&sha).unwrap();
+
+        for parent in commit.get_parents().into_iter().rev() {
+            let count = children.get_mut(&parent).unwrap();
+            *count -= 1;
+
+            if *count == 0 {
+                if let Some(parent_commit) = found.get(&parent) {
+                    match order {
+                        CommitOrder::Date => heap.push((commit_time(parent_commit), parent)),
+                        CommitOrder::Topo => ready.push(parent)
+                    }
+                }
+            }
+        }
+
+        commits.push((sha, commit));
+    }
+
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use tempdir::TempDir;
+    use crate::commit_graph::{ahead_behind, commit_walk, is_ancestor, merge_base, merge_bases, CommitOrder};
+    use crate::git_object::{GitCommit, GitObject};
+    use crate::object_utils::object_write;
+    use crate::repository::Repository;
+
+    /// Write a commit with the given parents and commit time straight into the object store, the
+    /// graph functions never look at the tree so every commit shares a made up one
+    fn dag_commit(repo: &Repository, shas: &mut HashMap<&'static str, String>, name: &'static str, parents: &[&str], time: i64) {
+        let parents = parents.iter().map(|parent| shas[parent].clone()).collect();
+        let author = format!("rust_git <test@example.com> {} +0000", time);
+        let commit = GitCommit::create("0".repeat(40), parents, author, name.to_string());
+        shas.insert(name, object_write(GitObject::Commit(commit), Some(repo)).unwrap());
+    }
+
+    /// A - B - C ----- F
+    ///      \         /
+    ///       D ----- E
+    /// with G and H the two criss-cross merges of C and E
+    fn build_dag(repo: &Repository) -> HashMap<&'static str, String> {
+        let mut shas = HashMap::new();
+        dag_commit(repo, &mut shas, "A", &[], 1);
+        dag_commit(repo, &mut shas, "B", &["A"], 2);
+        dag_commit(repo, &mut shas, "D", &["B"], 3);
+        dag_commit(repo, &mut shas, "C", &["B"], 4);
+        dag_commit(repo, &mut shas, "E", &["D"], 5);
+        dag_commit(repo, &mut shas, "F", &["C", "E"], 6);
+        dag_commit(repo, &mut shas, "G", &["C", "E"], 7);
+        dag_commit(repo, &mut shas, "H", &["E", "C"], 8);
+        shas
+    }
+
+    fn names(shas: &HashMap<&'static str, String>, walked: &[(String, GitCommit)]) -> Vec<&'static str> {
+        walked.iter().map(|(sha, _)| *shas.iter().find(|(_, candidate)| *candidate == sha).unwrap().0).collect()
+    }
+
+    #[test]
+    fn commit_graph_ancestry() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = Repository::create(tmp_dir.path().to_str().unwrap().into()).unwrap();
+        let shas = build_dag(&repo);
+
+        assert_eq!(merge_base(&repo, &shas["C"], &shas["E"]), Ok(Some(shas["B"].clone())));
+        assert_eq!(merge_base(&repo, &shas["F"], &shas["E"]), Ok(Some(shas["E"].clone())));
+        assert_eq!(merge_base(&repo, &shas["A"], &shas["A"]), Ok(Some(shas["A"].clone())));
+        assert_eq!(merge_bases(&repo, &shas["G"], &shas["H"]), Ok(vec![shas["E"].clone(), shas["C"].clone()]));
+
+        assert_eq!(is_ancestor(&repo, &shas["B"], &shas["F"]), Ok(true));
+        assert_eq!(is_ancestor(&repo, &shas["F"], &shas["B"]), Ok(false));
+        assert_eq!(is_ancestor(&repo, &shas["C"], &shas["E"]), Ok(false));
+
+        assert_eq!(ahead_behind(&repo, &shas["C"], &shas["E"]), Ok((1, 2)));
+        assert_eq!(ahead_behind(&repo, &shas["F"], &shas["E"]), Ok((2, 0)));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn commit_graph_walk_orders() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = Repository::create(tmp_dir.path().to_str().unwrap().into()).unwrap();
+        let shas = build_dag(&repo);
+
+        let date = commit_walk(&repo, &[shas["F"].clone()], &[], CommitOrder::Date).unwrap();
+        assert_eq!(names(&shas, &date), vec!["F", "E", "C", "D", "B", "A"]);
+
+        // topo order finishes the first parent's line before starting on the merged one
+        let topo = commit_walk(&repo, &[shas["F"].clone()], &[], CommitOrder::Topo).unwrap();
+        assert_eq!(names(&shas, &topo), vec!["F", "C", "E", "D", "B", "A"]);
+
+        let excluded = commit_walk(&repo, &[shas["F"].clone()], &[shas["C"].clone()], CommitOrder::Date).unwrap();
+        assert_eq!(names(&shas, &excluded), vec!["F", "E", "D"]);
+
+        let both = commit_walk(&repo, &[shas["G"].clone(), shas["H"].clone()], &[shas["F"].clone()], CommitOrder::Date).unwrap();
+        assert_eq!(names(&shas, &both), vec!["H", "G"]);
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -1,36 +1,83 @@
 use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq)]
 pub struct Config {
     path: String,
-    pub contents: ConfigContents
+    pub contents: ConfigContents,
+    // Which format the file was read in, and so is written back in
+    pub format: ConfigFormat,
+    // The ini file as read, so that the sections and keys we dont use are written back untouched
+    ini: String
 }
 
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ConfigFormat {
+    // The serde json of ConfigContents, used by repos we create ourselves
+    #[default]
+    Json,
+    // The ini style file of a repo created by git. A repo with this config is git compatible, so every
+    // file we write into it (refs, the index, trees and tags) uses the format git does
+    Ini
+}
+
+// Identity used for commits and tags when no user is set in the config
+const DEFAULT_IDENTITY: &str = "rust_git <test@example.com>";
+// What zlib uses when asked for its default level
+const DEFAULT_COMPRESSION: u32 = 6;
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct ConfigContents {
-    pub core: CoreContents
+    pub core: CoreContents,
+    // Optional so that configs written before the user section existed still load
+    pub user: Option<UserContents>
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct CoreContents {
     pub repository_format_version: i8,
     pub filemode: bool,
-    pub bare: bool
+    pub bare: bool,
+    // zlib level for loose objects, 0 (stored) to 9 (smallest) or -1 for the zlib default.
+    // Optional so that configs written before objects were compressed still load
+    pub compression: Option<i32>,
+    // How trees are encoded when written, reading detects either
+    pub tree_format: Option<TreeFormat>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeFormat {
+    // The serde json of the leaves, what every repo used before binary trees existed
+    #[default]
+    Json,
+    // git's '<mode> <path>\0<20 byte sha>' entries, so tree hashes match git's
+    Binary
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct UserContents {
+    pub name: String,
+    pub email: String
 }
 
 impl Config {
     pub fn new(path: String) -> Self {
         let config_contents = ConfigContents {
-            core: CoreContents::new()
+            core: CoreContents::new(),
+            user: None
         };
 
-        Config { path, contents: config_contents }
+        Config { path, contents: config_contents, format: ConfigFormat::Json, ini: String::new() }
     }
 
     pub fn write(&self) -> Result<(), String> {
-        let config_json = serde_json::to_string(&self.contents).or_else(|e| Err(e.to_string()))?;
-        fs::write(&self.path, config_json).or_else(|e| Err(e.to_string()))
+        let data = match self.format {
+            ConfigFormat::Json => serde_json::to_string(&self.contents).map_err(|e| e.to_string())?,
+            ConfigFormat::Ini => config_to_ini(&self.ini, &self.contents)
+        };
+        fs::write(&self.path, data).map_err(|e| e.to_string())
     }
 
     pub fn read(&mut self) -> Result<(), String> {
@@ -39,10 +86,54 @@ impl Config {
             Err(_) => return Err("Failed to read config".to_string())
         };
 
-        serde_json::from_str(data.as_str()).and_then(|contents| {
-            self.contents = contents;
-            Ok(())
-        }).or_else(|e| Err(e.to_string()))
+        // repos made by git have an ini style config, ours are json objects
+        if data.trim_start().starts_with('{') {
+            self.contents = serde_json::from_str(data.as_str()).map_err(|e| e.to_string())?;
+            self.format = ConfigFormat::Json;
+        } else {
+            self.contents = config_from_ini(&data)?;
+            self.format = ConfigFormat::Ini;
+            self.ini = data;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the repo was created by git, in which case everything we write into it has to be readable by git
+    pub fn git_compatible(&self) -> bool {
+        self.format == ConfigFormat::Ini
+    }
+
+    /// The 'name <email>' identity recorded as the author of commits and tags
+    pub fn identity(&self) -> String {
+        match &self.contents.user {
+            Some(user) => format!("{} <{}>", user.name, user.email),
+            None => DEFAULT_IDENTITY.to_string()
+        }
+    }
+
+    /// The zlib level to write loose objects with, as set by core.compression in git
+    pub fn compression_level(&self) -> Result<u32, String> {
+        match self.contents.core.compression {
+            None | Some(-1) => Ok(DEFAULT_COMPRESSION),
+            Some(level @ 0..=9) => Ok(level as u32),
+            Some(level) => Err(format!("Invalid compression level {}, expected -1 to 9", level))
+        }
+    }
+
+    pub fn tree_format(&self) -> TreeFormat {
+        if self.git_compatible() {
+            return TreeFormat::Binary;
+        }
+
+        self.contents.core.tree_format.unwrap_or_default()
+    }
+
+    /// The identity along with the current time, as written into the author,
+    /// committer and tagger fields e.g. 'name <email> 1700000000 +0000'
+    pub fn signature(&self) -> String {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        format!("{} {} +0000", self.identity(), timestamp)
     }
 }
 
@@ -51,15 +142,183 @@ impl CoreContents {
         CoreContents {
             repository_format_version: 0,
             filemode: false,
-            bare: false
+            bare: false,
+            compression: None,
+            tree_format: None
+        }
+    }
+}
+
+/// Parse the ini style config git writes, e.g.
+///
+/// [core]
+///     repositoryformatversion = 0
+///     bare = false
+///
+/// Section and key names are case insensitive. Anything we dont use, such as remotes, is skipped
+fn config_from_ini(data: &str) -> Result<ConfigContents, String> {
+    let mut contents = ConfigContents { core: CoreContents::new(), user: None };
+    let mut name = None;
+    let mut email = None;
+    let mut section = String::new();
+
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.strip_suffix(']').ok_or(format!("Bad section header on line {} of config", number + 1))?;
+            // subsections such as [remote "origin"] are kept whole so they never match the sections we read
+            section = header.trim().to_lowercase();
+            continue;
+        }
+
+        // a key with no value is shorthand for true
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_lowercase(), ini_value(value)),
+            None => (line.to_lowercase(), "true".to_string())
+        };
+
+        let invalid = format!("Invalid value '{}' for {}.{} in config", value, section, key);
+        match (section.as_str(), key.as_str()) {
+            ("core", "repositoryformatversion") => contents.core.repository_format_version = value.parse().map_err(|_| invalid.clone())?,
+            ("core", "filemode") => contents.core.filemode = ini_bool(&value).ok_or(invalid.clone())?,
+            ("core", "bare") => contents.core.bare = ini_bool(&value).ok_or(invalid.clone())?,
+            ("core", "compression") => contents.core.compression = Some(value.parse().map_err(|_| invalid.clone())?),
+            ("user", "name") => name = Some(value),
+            ("user", "email") => email = Some(value),
+            _ => {}
+        }
+    }
+
+    if let (Some(name), Some(email)) = (name, email) {
+        contents.user = Some(UserContents { name, email });
+    }
+
+    Ok(contents)
+}
+
+/// Write the values we use into the ini file that was read, which is empty for a new repo.
+///
+/// Only the lines of keys whose value changed are rewritten, and keys missing from the file are added
+/// to the end of their section, so everything else git keeps there (remotes, branches and so on) survives
+fn config_to_ini(ini: &str, contents: &ConfigContents) -> String {
+    let core = &contents.core;
+    let mut wanted = vec![
+        ("core", "repositoryformatversion", core.repository_format_version.to_string()),
+        ("core", "filemode", core.filemode.to_string()),
+        ("core", "bare", core.bare.to_string())
+    ];
+
+    if let Some(level) = core.compression {
+        wanted.push(("core", "compression", level.to_string()));
+    }
+
+    if let Some(user) = &contents.user {
+        wanted.push(("user", "name", user.name.clone()));
+        wanted.push(("user", "email", user.email.clone()));
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    // where the next key of each section would go, just after its last line
+    let mut section_ends: Vec<(String, usize)> = Vec::new();
+    let mut section = String::new();
+
+    for line in ini.lines() {
+        let trimmed = line.trim();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            section = header.trim_end_matches(']').trim().to_lowercase();
+        } else if !trimmed.is_empty() && !trimmed.starts_with('#') && !trimmed.starts_with(';') {
+            let (key, value) = match trimmed.split_once('=') {
+                Some((key, value)) => (key.trim(), ini_value(value)),
+                None => (trimmed, "true".to_string())
+            };
+
+            let position = wanted.iter().position(|(want_section, want_key, _)| *want_section == section && key.eq_ignore_ascii_case(want_key));
+            if let Some((_, _, new_value)) = position.map(|position| wanted.remove(position)) {
+                let unchanged = value == new_value || ini_bool(&value).is_some() && ini_bool(&value) == ini_bool(&new_value);
+                if !unchanged {
+                    lines.push(format!("\t{} = {}", key, ini_quote(&new_value)));
+                    section_ends.retain(|(name, _)| *name != section);
+                    section_ends.push((section.clone(), lines.len()));
+                    continue;
+                }
+            }
+        }
+
+        lines.push(line.to_string());
+        section_ends.retain(|(name, _)| *name != section);
+        section_ends.push((section.clone(), lines.len()));
+    }
+
+    // insert from the back so the positions of earlier sections stay right
+    section_ends.sort_by_key(|(_, end)| std::cmp::Reverse(*end));
+    for (name, end) in &section_ends {
+        let missing: Vec<String> = wanted.iter()
+            .filter(|(want_section, _, _)| want_section == name)
+            .map(|(_, key, value)| format!("\t{} = {}", key, ini_quote(value)))
+            .collect();
+        lines.splice(end..end, missing);
+    }
+
+    for name in ["core", "user"] {
+        if section_ends.iter().any(|(section, _)| section == name) {
+            continue;
+        }
+
+        let missing: Vec<String> = wanted.iter()
+            .filter(|(want_section, _, _)| *want_section == name)
+            .map(|(_, key, value)| format!("\t{} = {}", key, ini_quote(value)))
+            .collect();
+        if !missing.is_empty() {
+            lines.push(format!("[{}]", name));
+            lines.extend(missing);
+        }
+    }
+
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Quote a value which would otherwise lose everything from a comment character on when read back
+fn ini_quote(value: &str) -> String {
+    if value.contains(['#', ';']) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// The value of a key with any trailing comment and surrounding quotes removed
+fn ini_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+
+    for c in raw.trim().chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => break,
+            _ => value.push(c)
         }
     }
+
+    value.trim().to_string()
+}
+
+fn ini_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" | "" => Some(false),
+        _ => None
+    }
 }
 
 #[cfg(test)]
 mod test {
     use tempdir::TempDir;
-    use crate::config::{Config, ConfigContents, CoreContents};
+    use std::fs;
+    use crate::config::{Config, ConfigContents, ConfigFormat, CoreContents, TreeFormat, UserContents};
 
     #[test]
     fn config_write_read() {
@@ -67,8 +326,8 @@ mod test {
         let dummy_file_path = tmp_dir.path().join("some_file.txt");
         let dummy_file_path = dummy_file_path.as_path().to_str().unwrap().to_string();
 
-        let contents = ConfigContents { core: CoreContents::new() };
-        let config = Config { path: dummy_file_path.clone(), contents };
+        let contents = ConfigContents { core: CoreContents::new(), user: None };
+        let config = Config { path: dummy_file_path.clone(), contents, format: ConfigFormat::Json, ini: String::new() };
 
         let write_result = config.write();
         assert!(write_result.is_ok());
@@ -79,4 +338,40 @@ mod test {
 
         assert_eq!(config, config_read);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn config_ini() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let path = tmp_dir.path().join("config").to_str().unwrap().to_string();
+        let ini = "# written by git\n[core]\n\trepositoryformatversion = 0\n\tfileMode = true\n\tbare\n\
+                   [remote \"origin\"]\n\turl = https://example.com/repo.git\n\
+                   [user]\n\tname = \"Some Author\" ; a comment\n\temail = author@example.com\n";
+        fs::write(&path, ini).unwrap();
+
+        let mut config = Config::new(path.clone());
+        config.read().unwrap();
+        assert!(config.git_compatible());
+        assert_eq!(config.tree_format(), TreeFormat::Binary);
+        assert!(config.contents.core.filemode && config.contents.core.bare);
+        assert_eq!(config.contents.user, Some(UserContents { name: "Some Author".to_string(), email: "author@example.com".to_string() }));
+
+        // written back as ini so git can still read it, untouched when nothing changed
+        config.write().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), ini);
+        let mut config_read = Config::new(path.clone());
+        config_read.read().unwrap();
+        assert_eq!(config, config_read);
+
+        // only the changed keys are rewritten, new ones go at the end of their section and the rest is kept
+        config.contents.core.filemode = false;
+        config.contents.core.compression = Some(1);
+        config.contents.user = Some(UserContents { name: "Other; Author".to_string(), email: "author@example.com".to_string() });
+        config.write().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# written by git\n[core]\n\trepositoryformatversion = 0\n\tfileMode = false\n\tbare\n\
+                                                        \tcompression = 1\n[remote \"origin\"]\n\turl = https://example.com/repo.git\n\
+                                                        [user]\n\tname = \"Other; Author\"\n\temail = author@example.com\n");
+        let mut config_read = Config::new(path);
+        config_read.read().unwrap();
+        assert_eq!(config.contents, config_read.contents);
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use crate::git_object::GitObject;
+use crate::index::{Index, IndexEntry};
+use crate::object_utils::{object_find, object_read, tree_to_entries};
+use crate::repository::Repository;
+
+/// How many bytes to check for a NUL when deciding if a file is binary, the same heuristic git uses
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// A single step of an edit script turning the old lines into the new lines, each holding the
+/// index of the line it refers to on the old and/or new side
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize)
+}
+
+/// One side of a diff: a map of path to blob sha and mode, plus whether the content lives in the
+/// worktree rather than the object store
+pub struct DiffTarget {
+    files: HashMap<String, (String, String)>,
+    worktree: bool
+}
+
+/// The shortest edit script between two sequences of lines, using Myers' O(ND) algorithm
+/// from 'An O(ND) Difference Algorithm and Its Variations'.
+///
+/// We run the greedy forward search recording the furthest reaching x of every diagonal k for
+/// each edit distance d, then walk those snapshots backwards to recover the path. Step d only
+/// reads diagonals -d-1 to d+1, so only that slice is kept and the trace is O(D^2) rather than O((N+M)D)
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+
+    let mut v = vec![0isize; 2 * max + 3];
+    // trace[d][k + d + 1] is v[k] as it was when step d started
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[idx] = x;
+
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let at = |k: isize| v[(k + d + 1) as usize];
+
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(BINARY_CHECK_LEN).any(|byte| *byte == 0)
+}
+
+/// Render the differences between two files as unified diff hunks, each change surrounded by up
+/// to context unchanged lines. Changes closer together than twice the context share a hunk
+pub fn unified_hunks(old: &[u8], new: &[u8], context: usize) -> String {
+    let old_text = String::from_utf8_lossy(old);
+    let new_text = String::from_utf8_lossy(new);
+    let old_lines: Vec<&str> = old_text.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_text.split_inclusive('\n').collect();
+
+    let edits = diff_lines(&old_lines, &new_lines);
+    let changes: Vec<usize> = edits.iter().enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // group the changed positions into ranges of the edit script, merging those whose context overlaps
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for i in changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(edits.len());
+
+        match groups.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => groups.push((start, end))
+        }
+    }
+
+    // how many old and new lines come before each edit, which is where a hunk starting there begins
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in &edits {
+        positions.push((old_pos, new_pos));
+        match edit {
+            Edit::Equal(_, _) => {
+                old_pos += 1;
+                new_pos += 1;
+            },
+            Edit::Delete(_) => old_pos += 1,
+            Edit::Insert(_) => new_pos += 1
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end) in groups {
+        out.push_str(&unified_hunk(&edits[start..end], positions[start], &old_lines, &new_lines));
+    }
+
+    out
+}
+
+fn unified_hunk(edits: &[Edit], start: (usize, usize), old_lines: &[&str], new_lines: &[&str]) -> String {
+    let (mut old_start, mut new_start) = start;
+    let mut body = String::new();
+    let (mut old_count, mut new_count) = (0, 0);
+
+    for edit in edits {
+        let (marker, line) = match edit {
+            Edit::Equal(o, _) => {
+                old_count += 1;
+                new_count += 1;
+                (' ', old_lines[*o])
+            },
+            Edit::Delete(o) => {
+                old_count += 1;
+                ('-', old_lines[*o])
+            },
+            Edit::Insert(n) => {
+                new_count += 1;
+                ('+', new_lines[*n])
+            }
+        };
+
+        body.push(marker);
+        body.push_str(line);
+        if !line.ends_with('\n') {
+            body.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+
+    // Hunk headers are 1 based, except an empty side which points at the line before it
+    if old_count > 0 {
+        old_start += 1;
+    }
+    if new_count > 0 {
+        new_start += 1;
+    }
+
+    format!("@@ -{} +{} @@\n{}", hunk_range(old_start, old_count), hunk_range(new_start, new_count), body)
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        1 => format!("{}", start),
+        _ => format!("{},{}", start, count)
+    }
+}
+
+impl DiffTarget {
+    /// The files of a commit or tree
+    pub fn tree(repo: &Repository, name: &str) -> Result<Self, String> {
+        let sha = object_find(repo, name, Some("tree"), true)?;
+
+        Ok(DiffTarget {
+            files: tree_to_entries(repo, &sha)?,
+            worktree: false
+        })
+    }
+
+    /// The files staged in the index
+    pub fn index(repo: &Repository) -> Result<Self, String> {
+        let index = Index::read(repo)?;
+
+        Ok(DiffTarget {
+            files: index.entries.into_iter()
+                .filter(|entry| entry.flag_stage == 0)
+                .map(|entry| (entry.name.clone(), (entry.sha.clone(), entry.mode())))
+                .collect(),
+            worktree: false
+        })
+    }
+
+    /// The current contents of the files tracked in the index. Like git, untracked files are not part of the diff
+    /// and the staged mode is used unless the config says file modes matter
+    pub fn worktree(repo: &Repository) -> Result<Self, String> {
+        let index = Index::read(repo)?;
+        let mut files = HashMap::new();
+
+        for entry in index.entries {
+            if let Some(sha) = repo.hash_worktree_file(&entry.name)? {
+                let mode = if repo.conf.contents.core.filemode {
+                    IndexEntry::from_worktree(repo, sha.clone(), entry.name.clone()).mode()
+                } else {
+                    entry.mode()
+                };
+                files.insert(entry.name, (sha, mode));
+            }
+        }
+
+        Ok(DiffTarget { files, worktree: true })
+    }
+
+    fn read(&self, repo: &Repository, name: &str) -> Result<Vec<u8>, String> {
+        if self.worktree {
+            return fs::read(Path::new(&repo.worktree).join(name)).map_err(|e| e.to_string());
+        }
+
+        match object_read(repo, self.files[name].0.clone())? {
+            GitObject::Blob(blob) => Ok(blob.data.map(|data| data.to_vec()).unwrap_or_default()),
+            other => Err(format!("Expected {} to be a blob but found a {}", name, other.format_name()))
+        }
+    }
+}
+
+/// A git style diff of every file which differs between the two targets, in path order
+pub fn diff_targets(repo: &Repository, old: &DiffTarget, new: &DiffTarget, context: usize) -> Result<String, String> {
+    let names: BTreeSet<&String> = old.files.keys().chain(new.files.keys()).collect();
+    let mut out = String::new();
+
+    for name in names {
+        let (old_file, new_file) = (old.files.get(name), new.files.get(name));
+        if old_file == new_file {
+            continue;
+        }
+
+        let old_sha = old_file.map(|(sha, _)| sha);
+        let new_sha = new_file.map(|(sha, _)| sha);
+
+        let old_data = match old_sha {
+            Some(_) => old.read(repo, name)?,
+            None => Vec::new()
+        };
+        let new_data = match new_sha {
+            Some(_) => new.read(repo, name)?,
+            None => Vec::new()
+        };
+
+        out.push_str(&format!("diff --git a/{} b/{}\n", name, name));
+        // the mode goes on the index line unless it is one of the lines before it
+        let mut index_mode = String::new();
+        match (old_file, new_file) {
+            (None, Some((_, mode))) => out.push_str(&format!("new file mode {}\n", mode)),
+            (Some((_, mode)), None) => out.push_str(&format!("deleted file mode {}\n", mode)),
+            (Some((_, old_mode)), Some((_, new_mode))) if old_mode != new_mode => {
+                out.push_str(&format!("old mode {}\nnew mode {}\n", old_mode, new_mode));
+            },
+            (Some((_, mode)), _) => index_mode = format!(" {}", mode),
+            (None, None) => unreachable!("every name comes from one of the targets")
+        }
+
+        // only the mode changed, so there is nothing more to show
+        if old_sha == new_sha {
+            continue;
+        }
+        out.push_str(&format!("index {}..{}{}\n", short_sha(old_sha), short_sha(new_sha), index_mode));
+
+        let old_label = old_sha.map_or("/dev/null".to_string(), |_| format!("a/{}", name));
+        let new_label = new_sha.map_or("/dev/null".to_string(), |_| format!("b/{}", name));
+
+        if is_binary(&old_data) || is_binary(&new_data) {
+            out.push_str(&format!("Binary files {} and {} differ\n", old_label, new_label));
+            continue;
+        }
+
+        out.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+        out.push_str(&unified_hunks(&old_data, &new_data, context));
+    }
+
+    Ok(out)
+}
+
+fn short_sha(sha: Option<&String>) -> String {
+    match sha {
+        Some(sha) => sha[..7].to_string(),
+        None => "0000000".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::diff::{diff_lines, diff_targets, unified_hunks, DiffTarget, Edit};
+    use crate::repository::Repository;
+
+    #[test]
+    fn diff_lines_shortest_script() {
+        let old = vec!["a", "b", "c", "a", "b", "b", "a"];
+        let new = vec!["c", "b", "a", "b", "a", "c"];
+        let edits = diff_lines(&old, &new);
+
+        // the example from the Myers paper has an edit distance of 5
+        let changes = edits.iter().filter(|edit| !matches!(edit, Edit::Equal(_, _))).count();
+        assert_eq!(changes, 5);
+
+        // replaying the script has to rebuild the new sequence
+        let rebuilt: Vec<&str> = edits.iter().filter_map(|edit| match edit {
+            Edit::Equal(o, _) => Some(old[*o]),
+            Edit::Insert(n) => Some(new[*n]),
+            Edit::Delete(_) => None
+        }).collect();
+        assert_eq!(rebuilt, new);
+
+        assert_eq!(diff_lines::<&str>(&[], &[]), vec![]);
+        assert_eq!(diff_lines(&["a"], &[]), vec![Edit::Delete(0)]);
+        assert_eq!(diff_lines(&[], &["a"]), vec![Edit::Insert(0)]);
+    }
+
+    #[test]
+    fn unified_hunks_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\ntwelve\n";
+
+        let expected = "@@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+                        @@ -9,4 +9,4 @@\n 9\n 10\n 11\n-12\n+twelve\n";
+        assert_eq!(unified_hunks(old.as_bytes(), new.as_bytes(), 3), expected);
+
+        // with more context the two changes join into a single hunk
+        assert!(unified_hunks(old.as_bytes(), new.as_bytes(), 5).starts_with("@@ -1,12 +1,12 @@\n"));
+
+        assert_eq!(unified_hunks(b"", b"a\nb", 3), "@@ -0,0 +1,2 @@\n+a\n+b\n\\ No newline at end of file\n");
+        assert_eq!(unified_hunks(b"same\n", b"same\n", 3), "");
+    }
+
+    #[test]
+    fn diff_targets_worktree_index_and_trees() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "one\ntwo\n").unwrap();
+        fs::write(tmp_dir.path().join("image.bin"), [0u8, 1, 2]).unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_commit = repo.commit("First commit".to_string()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "one\n2\n").unwrap();
+        fs::write(tmp_dir.path().join("image.bin"), [0u8, 1, 3]).unwrap();
+
+        let index = DiffTarget::index(&repo).unwrap();
+        let worktree = DiffTarget::worktree(&repo).unwrap();
+        let diff = diff_targets(&repo, &index, &worktree, 3).unwrap();
+
+        assert!(diff.contains("--- a/first.txt\n+++ b/first.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n"));
+        assert!(diff.contains("Binary files a/image.bin and b/image.bin differ\n"));
+
+        // nothing is staged yet so the cached diff is empty
+        let head = DiffTarget::tree(&repo, "HEAD").unwrap();
+        assert_eq!(diff_targets(&repo, &head, &index, 3).unwrap(), "");
+
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let second_commit = repo.commit("Second commit".to_string()).unwrap();
+
+        let old = DiffTarget::tree(&repo, &first_commit).unwrap();
+        let new = DiffTarget::tree(&repo, &second_commit).unwrap();
+        assert_eq!(diff_targets(&repo, &old, &new, 3).unwrap(), diff);
+        assert!(diff.contains(&format!("index {}..", &old.files["first.txt"].0[..7])) && diff.contains(" 100644\n--- a/first.txt"));
+
+        // new and deleted files show their mode
+        fs::write(tmp_dir.path().join("new.txt"), "new\n").unwrap();
+        fs::remove_file(tmp_dir.path().join("image.bin")).unwrap();
+        repo.add(vec![tmp_dir.path().join("new.txt").to_str().unwrap().to_string()]).unwrap();
+        repo.rm(vec![tmp_dir.path().join("image.bin").to_str().unwrap().to_string()], true, false, false).unwrap();
+        let diff = diff_targets(&repo, &new, &DiffTarget::index(&repo).unwrap(), 3).unwrap();
+        assert!(diff.contains("diff --git a/new.txt b/new.txt\nnew file mode 100644\nindex 0000000.."));
+        assert!(diff.contains("diff --git a/image.bin b/image.bin\ndeleted file mode 100644\nindex "));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn diff_targets_mode_change() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let mut repo = Repository::create(tmp_dir_string.clone()).unwrap();
+        repo.conf.contents.core.filemode = true;
+
+        let script = tmp_dir.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        repo.commit("Script".to_string()).unwrap();
+
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let index = DiffTarget::index(&repo).unwrap();
+        let worktree = DiffTarget::worktree(&repo).unwrap();
+        assert_eq!(diff_targets(&repo, &index, &worktree, 3).unwrap(), "diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n");
+
+        tmp_dir.close().unwrap();
+    }
+}
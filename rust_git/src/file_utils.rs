@@ -1,5 +1,6 @@
+use std::env;
 use std::fs::create_dir_all;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use crate::repository::Repository;
 
 ///
@@ -52,6 +53,35 @@ pub fn repo_file(repository: &Repository, path: Vec<String>, mkdir: bool) -> Res
     })
 }
 
+/// Make a path absolute (relative to the current dir) and resolve any '.' or '..' components.
+/// This doesnt touch the filesystem so it also works for paths which no longer exist
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use rust_git::file_utils::normalize_path;
+/// assert_eq!(normalize_path(Path::new("/repo/./src/../README.md")), PathBuf::from("/repo/README.md"));
+/// ```
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().unwrap().join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            other => normalized.push(other)
+        }
+    }
+
+    normalized
+}
+
 #[cfg(test)]
 mod test {
     use std::path::{Path, PathBuf};
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::fs;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
@@ -8,6 +9,13 @@ use crate::object_utils::{object_find, object_read, object_write};
 use crate::refs::{Ref, RefType};
 use crate::repository::Repository;
 
+/// Mode of a leaf which points at another tree rather than a blob
+pub const TREE_MODE: &str = "040000";
+/// Mode of a blob which is checked out as an executable file
+pub const EXECUTABLE_MODE: &str = "100755";
+/// Mode of a blob holding the path a symlink points at
+pub const SYMLINK_MODE: &str = "120000";
+
 /// The GitWriteable trait represents an object which can be serialised and deserialised.
 /// This could have been avoided by directly using serdes traits but it was more in the spirit of the tutorial
 /// to create our own format for the key value pair structure of the commits even though ultimately it was not needed
@@ -42,11 +50,32 @@ pub enum GitObject {
     Blob(GitBlob)
 }
 
+impl GitObject {
+    /// The format name written into the header of the object file e.g. blob, commit
+    pub fn format_name(&self) -> String {
+        match self {
+            GitObject::Commit(_) => GitCommit::format_name(),
+            GitObject::Tree(_) => GitTree::format_name(),
+            GitObject::Tag(_) => GitTag::format_name(),
+            GitObject::Blob(_) => GitBlob::format_name()
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct GitCommit {
     pub data: KeyValuePairList
 }
 
+/// The author or committer line of a commit e.g. 'name <email> 1700000000 +0000'
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub timestamp: i64,
+    pub timezone: String
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GitTree {
     pub items: Vec<GitLeaf>
@@ -73,7 +102,14 @@ enum GitTagData {
         tag: String,
         // sha hash of object tagging
         object: String,
+        // Format of the tagged object, which git records in the tag. Defaulted so that tag objects written
+        // before it was recorded still load
+        #[serde(default)]
+        object_type: String,
         tagger: String,
+        // Defaulted so that tag objects written before messages were supported still load
+        #[serde(default)]
+        message: String
     },
     // Ultimately just a ref to a commit/tree/blob
     Lightweight {
@@ -125,6 +161,65 @@ impl GitWriteable<GitCommit> for GitCommit {
 }
 
 impl GitCommit {
+    /// Build a commit pointing at a tree, where the author is also used as the committer
+    /// and should already include the timestamp e.g. 'name <email> 1700000000 +0000'
+    pub fn create(tree: String, parents: Vec<String>, author: String, message: String) -> Self {
+        GitCommit::create_with_committer(tree, parents, author.clone(), author, message)
+    }
+
+    /// Build a commit whose author differs from the committer, as when a commit is cherry-picked
+    pub fn create_with_committer(tree: String, parents: Vec<String>, author: String, committer: String, message: String) -> Self {
+        let mut data = KeyValuePairList::new();
+        data.insert_pair("tree".to_string(), Bytes::from(tree));
+        for parent in parents {
+            data.insert_pair("parent".to_string(), Bytes::from(parent));
+        }
+        data.insert_pair("author".to_string(), Bytes::from(author));
+        data.insert_pair("committer".to_string(), Bytes::from(committer));
+        data.insert_contents(Bytes::from(message));
+
+        GitCommit { data }
+    }
+
+    /// The shas of the parents of this commit, empty for a root commit and more than one for a merge
+    pub fn get_parents(&self) -> Vec<String> {
+        match self.data.get(KeyValuePairKey::Key("parent".to_string())) {
+            None => vec![],
+            Some(KeyValuePairEntry::Singleton(parent)) => vec![String::from_utf8(parent.to_vec()).unwrap()],
+            Some(KeyValuePairEntry::List(parents)) => parents.iter()
+                .map(|parent| String::from_utf8(parent.to_vec()).unwrap())
+                .collect()
+        }
+    }
+
+    pub fn author(&self) -> Option<Signature> {
+        self.get_single("author").and_then(|author| Signature::parse(&author))
+    }
+
+    pub fn committer(&self) -> Option<Signature> {
+        self.get_single("committer").and_then(|committer| Signature::parse(&committer))
+    }
+
+    /// The message with the trailing newline added on serialisation removed
+    pub fn message(&self) -> String {
+        match self.data.get(KeyValuePairKey::Contents) {
+            Some(KeyValuePairEntry::Singleton(message)) => String::from_utf8_lossy(message).trim_end_matches('\n').to_string(),
+            _ => String::new()
+        }
+    }
+
+    /// The first line of the message, as shown by log --oneline
+    pub fn summary(&self) -> String {
+        self.message().lines().next().unwrap_or("").to_string()
+    }
+
+    fn get_single(&self, key: &str) -> Option<String> {
+        match self.data.get(KeyValuePairKey::Key(key.to_string())) {
+            Some(KeyValuePairEntry::Singleton(value)) => Some(String::from_utf8_lossy(value).to_string()),
+            _ => None
+        }
+    }
+
     pub fn get_tree_string(&self) -> Option<String> {
         let tree_entry = match self.data.get(KeyValuePairKey::Key("tree".to_string())) {
             None => return None,
@@ -148,8 +243,73 @@ impl GitCommit {
     }
 }
 
-// TODO: create a ::new() function which automatically populates the leaf key on add
+/// The 'name <email> timestamp timezone' form stored in a commit
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.identity(), self.timestamp, self.timezone)
+    }
+}
+
+impl Signature {
+    /// Parse 'name <email> timestamp timezone', returning None if any part is missing
+    pub fn parse(value: &str) -> Option<Self> {
+        let email_start = value.find('<')?;
+        let email_end = value.find('>')?;
+        let mut time_parts = value[email_end + 1..].split_whitespace();
+
+        Some(Signature {
+            name: value[..email_start].trim().to_string(),
+            email: value[email_start + 1..email_end].to_string(),
+            timestamp: time_parts.next()?.parse().ok()?,
+            timezone: time_parts.next().unwrap_or("+0000").to_string()
+        })
+    }
+
+    /// 'name <email>' without the time
+    pub fn identity(&self) -> String {
+        format!("{} <{}>", self.name, self.email)
+    }
+
+    /// The timezone as an offset in seconds from UTC
+    pub fn offset_seconds(&self) -> i64 {
+        let sign = if self.timezone.starts_with('-') { -1 } else { 1 };
+        let digits = self.timezone.trim_start_matches(['+', '-']);
+
+        match (digits.get(..2).and_then(|h| h.parse::<i64>().ok()), digits.get(2..4).and_then(|m| m.parse::<i64>().ok())) {
+            (Some(hours), Some(minutes)) => sign * (hours * 3600 + minutes * 60),
+            _ => 0
+        }
+    }
+}
+
 impl GitLeaf {
+    /// Create a leaf, populating the sort key from git's ordering rule where a
+    /// subtree sorts as though its name had a trailing '/'
+    pub fn new(mode: String, path: String, sha: String) -> Self {
+        let mut leaf = GitLeaf { mode, path, sha, sort_key: String::new() };
+        leaf.sort_key = leaf.git_sort_key();
+        leaf
+    }
+
+    pub fn is_tree(&self) -> bool {
+        self.mode == TREE_MODE
+    }
+
+    /// The sort key worked out from the mode and path, as leaves built directly may not have it populated
+    fn git_sort_key(&self) -> String {
+        if self.is_tree() { format!("{}/", self.path) } else { self.path.clone() }
+    }
+}
+
+/// The 20 raw bytes of a hex sha, as stored in binary trees and the index
+pub(crate) fn sha_to_bytes(sha: &str) -> Result<Vec<u8>, String> {
+    if sha.len() != 40 {
+        return Err(format!("Invalid sha {}", sha));
+    }
+
+    (0..40).step_by(2)
+        .map(|i| u8::from_str_radix(&sha[i..i + 2], 16).map_err(|_| format!("Invalid sha {}", sha)))
+        .collect()
 }
 
 impl GitTree {
@@ -186,9 +346,54 @@ impl GitTree {
         }
     }
 
-    // TODO: Add should sort by the leaf key
+    /// Encode the tree the way git does, a '<mode> <path>\0<20 byte sha>' entry per leaf with no
+    /// separator between them. git drops the leading zero of the tree mode and requires the
+    /// entries in sort key order, which isnt guaranteed for leaves pushed onto items directly
+    pub fn serialize_binary(&self) -> Result<Bytes, String> {
+        let mut leaves: Vec<&GitLeaf> = self.items.iter().collect();
+        leaves.sort_by_key(|leaf| leaf.git_sort_key());
+
+        let mut data = Vec::new();
+        for leaf in leaves {
+            data.extend_from_slice(leaf.mode.trim_start_matches('0').as_bytes());
+            data.push(b' ');
+            data.extend_from_slice(leaf.path.as_bytes());
+            data.push(0);
+            data.extend(sha_to_bytes(&leaf.sha)?);
+        }
+
+        Ok(Bytes::from(data))
+    }
+
+    /// Decode a tree in git's binary format, the reverse of serialize_binary
+    pub fn deserialize_binary(data: &[u8]) -> Result<GitTree, String> {
+        let mut tree = GitTree::new();
+        let mut rest = data;
+
+        while !rest.is_empty() {
+            let space = rest.iter().position(|&b| b == b' ').ok_or("Tree entry is missing its mode")?;
+            let nul = rest.iter().position(|&b| b == 0).ok_or("Tree entry is missing its path")?;
+            if nul < space || rest.len() < nul + 21 {
+                return Err("Tree entry is truncated".to_string());
+            }
+
+            let mode = String::from_utf8_lossy(&rest[..space]).to_string();
+            let path = String::from_utf8_lossy(&rest[space + 1..nul]).to_string();
+            let sha: String = rest[nul + 1..nul + 21].iter().map(|b| format!("{:02x}", b)).collect();
+
+            // keep the six digit mode so is_tree and the rest of the code see the same modes as json trees
+            tree.items.push(GitLeaf::new(format!("{:0>6}", mode), path, sha));
+            rest = &rest[nul + 21..];
+        }
+
+        Ok(tree)
+    }
+
+    /// Add a leaf keeping the items ordered by their sort key, so that a tree with
+    /// the same leaves always serialises to the same hash
     pub fn add(&mut self, git_leaf: GitLeaf) {
         self.items.push(git_leaf);
+        self.items.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
     }
 }
 
@@ -202,53 +407,168 @@ impl GitWriteable<GitTree> for GitTree {
     }
 
     fn serialize(&self) -> Bytes {
-        // TODO: sort by leaf keys before serialize to ensure hash of file with same keys is always the same
         Bytes::from(serde_json::to_string(self).unwrap())
     }
 
+    /// Either format can be read, as a json tree always starts with '{' and a binary one with a mode digit
     fn deserialize(data: Bytes) -> GitTree {
-        serde_json::from_str(&String::from_utf8(data.to_vec()).unwrap()).unwrap()
+        if data.first() == Some(&b'{') {
+            serde_json::from_slice(&data).unwrap()
+        } else {
+            GitTree::deserialize_binary(&data).unwrap()
+        }
     }
 }
 
 impl GitTag {
-    pub fn new_lightweight(tag: String, object: String, repo: &Repository) -> Self {
-        let sha = object_find(repo, &object, &"".to_string(), true);
+    pub fn new_lightweight(tag: String, object: String, repo: &Repository) -> Result<Self, String> {
+        let sha = object_find(repo, &object, None, true)?;
 
         let data = GitTagData::Lightweight { tag, object: sha };
-        GitTag { data }
+        Ok(GitTag { data })
+    }
+
+    /// An annotated tag, where the tagger is taken from the user in the repo config
+    pub fn new_object(tag: String, object: String, message: String, repo: &Repository) -> Result<Self, String> {
+        let sha = object_find(repo, &object, None, true)?;
+        let object_type = object_read(repo, sha.clone())?.format_name();
+
+        let data = GitTagData::Object { object: sha, object_type, tag, tagger: repo.conf.signature(), message };
+        Ok(GitTag { data })
+    }
+
+    pub fn name(&self) -> String {
+        match &self.data {
+            GitTagData::Object { tag, .. } | GitTagData::Lightweight { tag, .. } => tag.clone()
+        }
+    }
+
+    /// The sha of the object this tag points at, which may itself be another tag
+    pub fn target(&self) -> String {
+        match &self.data {
+            GitTagData::Object { object, .. } | GitTagData::Lightweight { object, .. } => object.clone()
+        }
+    }
+
+    pub fn tagger(&self) -> Option<String> {
+        match &self.data {
+            GitTagData::Object { tagger, .. } => Some(tagger.clone()),
+            GitTagData::Lightweight { .. } => None
+        }
+    }
+
+    pub fn message(&self) -> Option<String> {
+        match &self.data {
+            GitTagData::Object { message, .. } => Some(message.clone()),
+            GitTagData::Lightweight { .. } => None
+        }
     }
 
-    pub fn new_object(tag: String, object: String, repo: &Repository) -> Self {
-        let sha = object_find(repo, &object, &"".to_string(), true);
+    /// Follow the target of the tag through any further tags to the sha of the
+    /// commit, tree or blob that is ultimately tagged
+    pub fn peel(&self, repo: &Repository) -> Result<String, String> {
+        let mut sha = self.target();
+
+        while let GitObject::Tag(tag) = object_read(repo, sha.clone())? {
+            sha = tag.target();
+        }
 
-        let data = GitTagData::Object { object: sha, tag, tagger: "rust_git <test@example.com>".to_string() };
-        GitTag { data }
+        Ok(sha)
     }
 
-    pub fn write(&self, repo: &Repository) -> Result<(), String> {
+    /// Create the refs/tags/<name> ref for the tag, for an annotated tag we first write the tag
+    /// object and point the ref at that, for a lightweight tag the ref points straight at the target.
+    ///
+    /// An existing tag of the same name is only replaced if force is set
+    pub fn write(&self, repo: &Repository, force: bool) -> Result<(), String> {
+        let tag = self.name();
+        if !force && Ref::new(GitTag::ref_name(&tag)).exists(repo) {
+            return Err(format!("tag '{}' already exists", tag));
+        }
+
         match &self.data {
             GitTagData::Object { tag, .. } => self.write_object(tag, repo),
             GitTagData::Lightweight { tag, object } => self.write_lightweight(tag, object, repo)
         }
     }
 
-    fn write_object(&self, tag: &String, repo: &Repository) -> Result<(), String> {
+    /// The names of all tags in the repo, sorted
+    pub fn list(repo: &Repository) -> Vec<String> {
+        let mut tags: Vec<String> = Ref::all_refs(repo).into_iter()
+            .filter_map(|reference| reference.name.strip_prefix("refs/tags/").map(|name| name.to_string()))
+            .collect();
+
+        tags.sort();
+        tags
+    }
+
+    /// Delete the ref of a tag, the tag object itself is left in the object store
+    pub fn delete(repo: &Repository, tag: &str) -> Result<(), String> {
+        let reference = Ref::new(GitTag::ref_name(tag));
+        if !reference.exists(repo) {
+            return Err(format!("tag '{}' not found", tag));
+        }
+
+        reference.delete(repo)
+    }
+
+    /// Encode an annotated tag the way git does, as the header lines 'object', 'type', 'tag' and 'tagger'
+    /// followed by a blank line and the message. A lightweight tag is only a ref so has no encoding
+    pub fn serialize_git(&self) -> Result<Bytes, String> {
+        let GitTagData::Object { tag, object, object_type, tagger, message } = &self.data else {
+            return Err("Only annotated tags are written as objects".to_string());
+        };
+
+        let mut data = KeyValuePairList::new();
+        data.insert_pair("object".to_string(), Bytes::from(object.clone()));
+        // tags written before the type was recorded could only tag what object_find resolved, which was a commit
+        let object_type = if object_type.is_empty() { "commit" } else { object_type };
+        data.insert_pair("type".to_string(), Bytes::from(object_type.to_string()));
+        data.insert_pair("tag".to_string(), Bytes::from(tag.clone()));
+        data.insert_pair("tagger".to_string(), Bytes::from(tagger.clone()));
+        data.insert_contents(Bytes::from(message.clone()));
+
+        Ok(data.into_bytes())
+    }
+
+    pub fn deserialize_git(data: Bytes) -> Result<GitTag, String> {
+        let list = KeyValuePairList::from(data)?;
+        let field = |key: &str| match list.get(KeyValuePairKey::Key(key.to_string())) {
+            Some(KeyValuePairEntry::Singleton(value)) => Ok(String::from_utf8(value.to_vec()).map_err(|e| e.to_string())?),
+            _ => Err(format!("Tag is missing its {} line", key))
+        };
+
+        // the message is written with a trailing new line, which is not part of it
+        let message = match list.get(KeyValuePairKey::Contents) {
+            Some(KeyValuePairEntry::Singleton(value)) => String::from_utf8(value.to_vec()).map_err(|e| e.to_string())?,
+            _ => String::new()
+        };
+        let message = message.strip_suffix('\n').unwrap_or(&message).to_string();
+
+        let data = GitTagData::Object { object: field("object")?, object_type: field("type")?, tag: field("tag")?, tagger: field("tagger")?, message };
+        Ok(GitTag { data })
+    }
+
+    fn ref_name(tag: &str) -> String {
+        format!("refs/tags/{}", tag)
+    }
+
+    fn write_object(&self, tag: &str, repo: &Repository) -> Result<(), String> {
         object_write(GitObject::Tag(self.clone()), Some(repo))
-            .map_err(|e| e.into())
             .and_then(|tag_sha| {
                 println!("Creating indirect tag {} to {}", tag, tag_sha);
-                let mut reference = Ref::new(format!("refs/tags/{}", tag));
+                let mut reference = Ref::new(GitTag::ref_name(tag));
                 reference.add_target(RefType::Direct(tag_sha.to_owned()));
 
-                reference.write(&repo)
+                reference.write(repo)
             })
     }
 
-    fn write_lightweight(&self, tag: &String, object: &String, repo: &Repository) -> Result<(), String> {
-        let mut reference = Ref::new(format!("refs/tags/{}", tag));
+    fn write_lightweight(&self, tag: &str, object: &str, repo: &Repository) -> Result<(), String> {
+        println!("Creating lightweight tag {} to {}", tag, object);
+        let mut reference = Ref::new(GitTag::ref_name(tag));
         reference.add_target(RefType::Direct(object.to_owned()));
-        reference.write(&repo)
+        reference.write(repo)
     }
 }
 
@@ -265,7 +585,170 @@ impl GitWriteable<GitTag> for GitTag {
         Bytes::from(serde_json::to_string(self).unwrap())
     }
 
+    /// Either format can be read, as a json tag always starts with '{' and a git one with its object line
     fn deserialize(data: Bytes) -> GitTag {
-        serde_json::from_str(&String::from_utf8(data.to_vec()).unwrap()).unwrap()
+        if data.first() == Some(&b'{') {
+            serde_json::from_slice(&data).unwrap()
+        } else {
+            GitTag::deserialize_git(data).unwrap()
+        }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use bytes::Bytes;
+    use crate::config::{TreeFormat, UserContents};
+    use crate::git_object::{GitCommit, GitLeaf, GitObject, GitTag, GitTree, GitWriteable, Signature};
+    use crate::index::{Index, IndexEntry};
+    use crate::object_utils::{object_find, object_read, object_write, tree_from_index, tree_to_dict};
+    use crate::refs::{Ref, RefType};
+    use crate::repository::Repository;
+
+    #[test]
+    fn commit_accessors() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        let author = "A U Thor <author@example.com> 1700000000 -0130".to_string();
+        let commit = GitCommit::create("a".repeat(40), vec!["b".repeat(40), "c".repeat(40)], author, "Summary line\n\nBody".to_string());
+        let sha = object_write(GitObject::Commit(commit), Some(&repo)).unwrap();
+
+        let commit = match object_read(&repo, sha).unwrap() {
+            GitObject::Commit(commit) => commit,
+            other => panic!("Expected a commit, found {:?}", other)
+        };
+
+        let expected = Signature {
+            name: "A U Thor".to_string(),
+            email: "author@example.com".to_string(),
+            timestamp: 1700000000,
+            timezone: "-0130".to_string()
+        };
+        assert_eq!(commit.author(), Some(expected.clone()));
+        assert_eq!(commit.committer(), Some(expected.clone()));
+        assert_eq!(expected.offset_seconds(), -5400);
+        assert_eq!(expected.to_string(), "A U Thor <author@example.com> 1700000000 -0130");
+        assert_eq!(commit.get_parents(), vec!["b".repeat(40), "c".repeat(40)]);
+        assert_eq!(commit.message(), "Summary line\n\nBody");
+        assert_eq!(commit.summary(), "Summary line");
+        assert_eq!(Signature::parse("no email 1700000000 +0000"), None);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn tree_binary_format() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let mut repo = Repository::create(tmp_dir_string).unwrap();
+
+        let hello = "ce013625030ba8dba906f756967f9e9ca394464a";
+        let nested = "79c53955ef856f16f2107446bc721c8879a1bd2e";
+        let mut index = Index::new();
+        index.add_entry(IndexEntry::from_blob(hello.to_string(), "a.txt".to_string()));
+        index.add_entry(IndexEntry::from_blob(nested.to_string(), "a/b".to_string()));
+
+        let json_sha = tree_from_index(&repo, &index).unwrap();
+
+        // the same tree git write-tree gives, where the 'a' dir sorts after 'a.txt' as though it were 'a/'
+        repo.conf.contents.core.tree_format = Some(TreeFormat::Binary);
+        let binary_sha = tree_from_index(&repo, &index).unwrap();
+        assert_eq!(binary_sha, "ead944c1fb5db1dd7308d54e7f8be90d8a9751d3");
+
+        let tree = match object_read(&repo, binary_sha.clone()).unwrap() {
+            GitObject::Tree(tree) => tree,
+            other => panic!("Expected a tree, got {:?}", other)
+        };
+        let paths: Vec<(&str, &str)> = tree.items.iter().map(|leaf| (leaf.mode.as_str(), leaf.path.as_str())).collect();
+        assert_eq!(paths, vec![("100644", "a.txt"), ("040000", "a")]);
+        assert!(tree.serialize_binary().unwrap().starts_with(b"100644 a.txt\0"));
+
+        // trees written as json before the switch are still read, and both flatten to the same files
+        assert_eq!(tree_to_dict(&repo, &json_sha, None).unwrap(), tree_to_dict(&repo, &binary_sha, None).unwrap());
+        assert_eq!(tree_to_dict(&repo, &binary_sha, None).unwrap().get("a/b"), Some(&nested.to_string()));
+
+        // leaves pushed out of order are still written in git's order
+        let mut unsorted = GitTree::new();
+        unsorted.items.push(GitLeaf::new("100644".to_string(), "b".to_string(), hello.to_string()));
+        unsorted.items.push(GitLeaf::new("100644".to_string(), "a".to_string(), hello.to_string()));
+        let decoded = GitTree::deserialize(unsorted.serialize_binary().unwrap());
+        assert_eq!(decoded.items.iter().map(|leaf| leaf.path.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert!(GitTree::deserialize_binary(b"100644 truncated\0abc").is_err());
+        assert_eq!(GitTree::deserialize(Bytes::new()).items.len(), 0);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn tag_object_round_trip() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let mut repo = Repository::create(tmp_dir_string.clone()).unwrap();
+        repo.conf.contents.user = Some(UserContents { name: "Tagger".to_string(), email: "tagger@example.com".to_string() });
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let commit_sha = repo.commit("First commit".to_string()).unwrap();
+
+        let tag = GitTag::new_object("v1.0".to_string(), "HEAD".to_string(), "Release 1.0".to_string(), &repo).unwrap();
+        let tag_sha = object_write(GitObject::Tag(tag), Some(&repo)).unwrap();
+
+        let read_tag = match object_read(&repo, tag_sha.clone()).unwrap() {
+            GitObject::Tag(tag) => tag,
+            other => panic!("Expected a tag, found {:?}", other)
+        };
+        assert_eq!(read_tag.name(), "v1.0");
+        assert_eq!(read_tag.message(), Some("Release 1.0".to_string()));
+        assert!(read_tag.tagger().unwrap().starts_with("Tagger <tagger@example.com> "));
+        assert_eq!(read_tag.peel(&repo), Ok(commit_sha.clone()));
+
+        // a tag of a tag still peels all the way to the commit
+        let outer_tag = GitTag::new_object("outer".to_string(), tag_sha.clone(), "".to_string(), &repo).unwrap();
+        assert_eq!(outer_tag.peel(&repo), Ok(commit_sha.clone()));
+        assert_eq!(object_find(&repo, &tag_sha, Some("commit"), true), Ok(commit_sha));
+        assert_eq!(object_find(&repo, &tag_sha, Some("tag"), false), Ok(tag_sha));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn tag_write_list_delete() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first_sha = repo.commit("First commit".to_string()).unwrap();
+
+        // a lightweight tag is just a ref straight to the commit
+        let lightweight = GitTag::new_lightweight("v1.0".to_string(), "HEAD".to_string(), &repo).unwrap();
+        assert!(lightweight.write(&repo, false).is_ok());
+        assert_eq!(Ref::new("refs/tags/v1.0".to_string()).fully_resolve(&repo), RefType::Direct(first_sha.clone()));
+
+        let annotated = GitTag::new_object("release/v1.0".to_string(), "HEAD".to_string(), "Release".to_string(), &repo).unwrap();
+        assert!(annotated.write(&repo, false).is_ok());
+        assert_eq!(object_find(&repo, "release/v1.0", Some("tag"), false).map(|_| ()), Ok(()));
+        assert_eq!(GitTag::list(&repo), vec!["release/v1.0", "v1.0"]);
+
+        // existing tags are only moved when forced
+        fs::write(tmp_dir.path().join("first.txt"), "first file changed").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let second_sha = repo.commit("Second commit".to_string()).unwrap();
+
+        let moved = GitTag::new_lightweight("v1.0".to_string(), "HEAD".to_string(), &repo).unwrap();
+        assert!(moved.write(&repo, false).is_err());
+        assert!(moved.write(&repo, true).is_ok());
+        assert_eq!(object_find(&repo, "v1.0", None, true), Ok(second_sha));
+
+        assert!(GitTag::delete(&repo, "v1.0").is_ok());
+        assert!(GitTag::delete(&repo, "v1.0").is_err());
+        assert_eq!(GitTag::list(&repo), vec!["release/v1.0"]);
+
+        tmp_dir.close().unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use crate::branch_utils::branch_get_active;
+use crate::git_object::GitObject;
+use crate::log::{log_revisions, LogWalk};
+use crate::object_utils::{object_find, object_read};
+use crate::refs::Ref;
+use crate::repository::Repository;
+
+/// Builds a Graphviz DOT description of the history, like the log command of the WYAG tutorial
+/// https://wyag.thb.lt/#cmd-log but with refs drawn as well, so 'dot -Tpng' can turn it into a picture.
+///
+/// Commits are boxes labelled with their short sha and first message line, pointing at their parents.
+/// Branches and tags are drawn as ellipses pointing at the commits they name, and HEAD points at the
+/// current branch. With objects set each commit also points at its tree, and trees at their entries.
+#[derive(Default)]
+pub struct GraphvizExport {
+    lines: Vec<String>,
+    seen: HashSet<String>
+}
+
+impl GraphvizExport {
+    /// Produce the DOT graph of every commit reachable from the revisions, which can use the same
+    /// a..b, a...b and ^a exclusions as log
+    pub fn export(mut self, repo: &Repository, revisions: &[String], objects: bool) -> Result<String, String> {
+        let (include, exclude) = log_revisions(repo, revisions)?;
+        let walk = LogWalk::new(repo, &include, &exclude)?;
+        let commits: HashSet<&String> = walk.commits.iter().map(|(sha, _)| sha).collect();
+
+        for (sha, commit) in &walk.commits {
+            self.node(&format!("c_{}", sha), &format!("{}\\n{}", &sha[..7], escape(&commit.summary())), "shape=box");
+
+            // parents the range excluded have no node, so they get no edge either
+            for (i, parent) in commit.get_parents().iter().enumerate().filter(|(_, parent)| commits.contains(parent)) {
+                // the first parent is the line of history the commit was made on, the rest were merged in
+                let style = if i == 0 { "" } else { " [style=dashed, label=\"merge\"]" };
+                self.lines.push(format!("  c_{} -> c_{}{};", sha, parent, style));
+            }
+
+            if objects {
+                let tree = commit.get_tree_string().ok_or(format!("Commit {} has no tree", sha))?;
+                self.lines.push(format!("  c_{} -> t_{};", sha, tree));
+                self.tree(repo, &tree)?;
+            }
+        }
+
+        for reference in Ref::all_refs(repo) {
+            let target = match object_find(repo, &reference.name, Some("commit"), true) {
+                Ok(target) if commits.contains(&target) => target,
+                _ => continue
+            };
+
+            let (label, colour) = match reference.name.split_once('/').map(|(_, rest)| rest.split_once('/')) {
+                Some(Some(("heads", name))) => (name.to_string(), "lightblue"),
+                Some(Some(("tags", name))) => (format!("tag: {}", name), "lightyellow"),
+                _ => (reference.name.clone(), "lightgrey")
+            };
+
+            let id = ref_id(&reference.name);
+            self.node(&id, &escape(&label), &format!("shape=ellipse, style=filled, fillcolor={}", colour));
+            self.lines.push(format!("  {} -> c_{};", id, target));
+        }
+
+        // HEAD points at the branch it is on, or straight at a commit when detached
+        let head_target = match branch_get_active(repo) {
+            Some(branch) => Some(ref_id(&format!("refs/heads/{}", branch))),
+            None => object_find(repo, "HEAD", Some("commit"), true).ok().map(|sha| format!("c_{}", sha))
+        };
+
+        if let Some(target) = head_target.filter(|target| self.seen.contains(target)) {
+            self.node("HEAD", "HEAD", "shape=ellipse, style=filled, fillcolor=orange");
+            self.lines.push(format!("  HEAD -> {};", target));
+        }
+
+        Ok(format!("digraph history {{\n  rankdir=RL;\n{}\n}}\n", self.lines.join("\n")))
+    }
+
+    fn tree(&mut self, repo: &Repository, sha: &str) -> Result<(), String> {
+        if !self.node(&format!("t_{}", sha), &format!("tree\\n{}", &sha[..7]), "shape=folder") {
+            return Ok(());
+        }
+
+        let tree = match object_read(repo, sha.to_string())? {
+            GitObject::Tree(tree) => tree,
+            other => return Err(format!("Expected {} to be a tree but found a {}", sha, other.format_name()))
+        };
+
+        for leaf in tree.items {
+            let id = if leaf.is_tree() {
+                self.tree(repo, &leaf.sha)?;
+                format!("t_{}", leaf.sha)
+            } else {
+                self.node(&format!("b_{}", leaf.sha), &format!("blob\\n{}", &leaf.sha[..7]), "shape=note");
+                format!("b_{}", leaf.sha)
+            };
+
+            self.lines.push(format!("  t_{} -> {} [label=\"{}\"];", sha, id, escape(&leaf.path)));
+        }
+
+        Ok(())
+    }
+
+    /// Add a node the first time it is seen, returning false if it already existed
+    fn node(&mut self, id: &str, label: &str, attributes: &str) -> bool {
+        if !self.seen.insert(id.to_string()) {
+            return false;
+        }
+
+        self.lines.push(format!("  {} [label=\"{}\", {}];", id, label, attributes));
+        true
+    }
+}
+
+/// Ref names can contain characters which arent valid in a DOT id, so they are quoted
+fn ref_id(name: &str) -> String {
+    format!("\"{}\"", escape(name))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::branch_utils::branch_create;
+    use crate::git_object::GitTag;
+    use crate::graphviz::GraphvizExport;
+    use crate::repository::Repository;
+
+    #[test]
+    fn graphviz_commits_refs_and_objects() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        let first = repo.commit("First \"quoted\" commit".to_string()).unwrap();
+        GitTag::new_lightweight("v1".to_string(), first.clone(), &repo).unwrap().write(&repo, false).unwrap();
+        branch_create(&repo, "feature", "HEAD", false).unwrap();
+
+        fs::write(tmp_dir.path().join("second.txt"), "second file").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let second = repo.commit("Second commit".to_string()).unwrap();
+
+        let dot = GraphvizExport::default().export(&repo, &["HEAD".to_string()], false).unwrap();
+        assert!(dot.starts_with("digraph history {\n"));
+        assert!(dot.contains(&format!("  c_{} [label=\"{}\\nFirst \\\"quoted\\\" commit\", shape=box];", first, &first[..7])));
+        assert!(dot.contains(&format!("  c_{} -> c_{};", second, first)));
+        assert!(dot.contains(&format!("  \"refs/heads/feature\" -> c_{};", first)));
+        assert!(dot.contains(&format!("  \"refs/tags/v1\" -> c_{};", first)));
+        assert!(dot.contains("  HEAD -> \"refs/heads/master\";"));
+        assert!(!dot.contains("t_"));
+
+        // a range leaves out the excluded commits and the refs pointing at them
+        let dot = GraphvizExport::default().export(&repo, &["feature..master".to_string()], false).unwrap();
+        assert!(!dot.contains(&format!("c_{} [", first)));
+        assert!(!dot.contains("refs/tags/v1"));
+        assert!(!dot.contains(&format!("c_{}", first)));
+
+        let dot = GraphvizExport::default().export(&repo, &["HEAD".to_string()], true).unwrap();
+        assert!(dot.contains("[label=\"first.txt\"]"));
+        assert!(dot.contains("[label=\"second.txt\"]"));
+        assert!(dot.contains("shape=note"));
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -1,8 +1,8 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::path::Path;
 use glob::Pattern;
+use crate::git_object::GitObject;
 use crate::index::Index;
+use crate::object_utils::object_read;
 use crate::repository::Repository;
 
 #[derive(PartialEq, Debug)]
@@ -37,22 +37,21 @@ impl Ignore {
     /// .gitignore as well as .gitignore files in the index itself which
     /// adds complexity https://wyag.thb.lt/#cmd-check-ignore
     ///
+    /// The rules come from the staged blob, so a .gitignore deleted from the worktree still applies
     pub fn read(repo: &Repository) -> Self {
-        match Index::read(&repo) {
-            Ok(index) => {
-                if let Some(entry) = index.get_gitignore() {
-                    println!("Opening gitignore file: {:?}", entry.name);
-                    let file = File::open(entry.name).unwrap();
-                    let reader = BufReader::new(file);
-
-                    let lines: Vec<String> = reader.lines().map(|line| line.unwrap()).collect();
-
-                    Ignore::parse_file(lines)
-                } else {
-                    Ignore::new(vec![])
-                }
+        let entry = match Index::read(repo).map(|index| index.get_gitignore()) {
+            Ok(Some(entry)) => entry,
+            _ => return Ignore::new(vec![])
+        };
+
+        match object_read(repo, entry.sha) {
+            Ok(GitObject::Blob(blob)) => {
+                let data = blob.data.unwrap_or_default();
+                let lines: Vec<String> = String::from_utf8_lossy(&data).lines().map(|line| line.to_string()).collect();
+
+                Ignore::parse_file(lines)
             },
-            Err(_err) => Ignore::new(vec![])
+            _ => Ignore::new(vec![])
         }
     }
 
@@ -110,7 +109,6 @@ impl Ignore {
         for rule in &self.rules {
             match rule {
                 IgnoreRule::Normal(rule) => {
-                    println!("Checking Normal rule: {:?}", rule);
                     let pattern = Pattern::new(&rule);
                     matches.push(pattern.unwrap().matches_path(&path));
                 },
@@ -118,7 +116,6 @@ impl Ignore {
             }
         }
 
-        println!("Matches computed for {:?} are:{:?}", path, matches);
         let all_false = matches.iter().all(|x| !x.to_owned());
 
         if all_false {
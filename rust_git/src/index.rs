@@ -1,33 +1,72 @@
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
-use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha1::{Digest, Sha1};
 use crate::file_utils::repo_file;
+use crate::git_object::sha_to_bytes;
 use crate::repository::Repository;
 
+// Magic bytes which start the binary index, 'dircache'
+const INDEX_SIGNATURE: &[u8] = b"DIRC";
+// Every entry has this many bytes of fixed size fields before its path
+const ENTRY_FIXED_SIZE: usize = 62;
+// Flag bits of an entry, the low 12 bits hold the length of the path
+const FLAG_ASSUME_VALID: u16 = 0x8000;
+const FLAG_EXTENDED: u16 = 0x4000;
+const FLAG_STAGE_SHIFT: u16 = 12;
+const FLAG_NAME_MASK: u16 = 0xfff;
+// Bits of the second flags field which extended entries have from version 3
+const EXTENDED_SKIP_WORKTREE: u16 = 0x4000;
+const EXTENDED_INTENT_TO_ADD: u16 = 0x2000;
+// Extensions which cache facts about the entries. We dont keep them up to date as the entries change,
+// so rather than leave them stale they are dropped when writing and git rebuilds them
+const CACHE_EXTENSIONS: [&str; 5] = ["TREE", "UNTR", "FSMN", "EOIE", "IEOT"];
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Index {
+    // 2 to 4, as documented in https://git-scm.com/docs/index-format
     version: i32,
-    pub entries: Vec<IndexEntry>
+    pub entries: Vec<IndexEntry>,
+    // Defaulted so that json indexes written before extensions were kept still load
+    #[serde(default)]
+    pub extensions: Vec<IndexExtension>
+}
+
+/// A block of data after the entries, kept as is so that the extensions other tools write survive us
+/// rewriting the index
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexExtension {
+    pub signature: String,
+    pub data: Vec<u8>
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct IndexEntry {
-    // TODO: is this the right way to store time?
+    // When the metadata of the file last changed (its ctime), or its creation time off unix
     pub time: SystemTime,
     pub mtime: SystemTime,
-    pub dev: String,
-    // TODO: could this be u32?
-    pub ino: i32,
+    // Older indexes stored the device as an always empty string
+    #[serde(deserialize_with = "deserialize_dev")]
+    pub dev: u32,
+    pub ino: u32,
     pub model_type: ModelType,
     pub model_perms: i32,
-    pub uid: i32,
-    pub gid: i32,
+    pub uid: u32,
+    pub gid: u32,
     // Size of the object in bytes
     pub fsize: u64,
     pub sha: String,
     pub flag_assume_valid: bool,
-    pub flag_stage: bool,
+    // 0 for a normal entry, during a conflicted merge 1 is the base version, 2 ours and 3 theirs
+    #[serde(deserialize_with = "deserialize_stage")]
+    pub flag_stage: u8,
+    // Set by sparse checkouts for entries which are not in the worktree, only in index version 3 and up
+    #[serde(default)]
+    pub flag_skip_worktree: bool,
+    // Set by 'add -N' for paths which will be added but have no staged contents yet, version 3 and up
+    #[serde(default)]
+    pub flag_intent_to_add: bool,
     // Full path of the object
     pub name: String
 }
@@ -44,26 +83,160 @@ pub enum ModelType {
 
 impl Index {
     pub fn new() -> Self {
-        Index { version: 2, entries: Vec::new() }
+        Index { version: 2, entries: Vec::new(), extensions: Vec::new() }
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Choose the version the index is written as. Version 3 is used anyway when an entry needs the
+    /// extended flags, and version 4 compresses each path against the path before it
+    pub fn set_version(&mut self, version: i32) -> Result<(), String> {
+        if !(2..=4).contains(&version) {
+            return Err(format!("Unsupported index version {}, expected 2 to 4", version));
+        }
+
+        self.version = version;
+        Ok(())
     }
 
     pub fn add_entry(&mut self, entry: IndexEntry) {
         self.entries.push(entry);
     }
 
+    /// Remove the entry with the given name (relative to the worktree) if present,
+    /// returning true if anything was removed
+    pub fn remove_entry(&mut self, name: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+        before != self.entries.len()
+    }
+
+    pub fn get_entry(&self, name: &str) -> Option<&IndexEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn get_stage(&self, name: &str, stage: u8) -> Option<&IndexEntry> {
+        self.entries.iter().find(|entry| entry.name == name && entry.flag_stage == stage)
+    }
+
+    /// The paths with unresolved merge conflicts, in index order
+    pub fn conflicted_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.entries.iter()
+            .filter(|entry| entry.flag_stage != 0)
+            .map(|entry| entry.name.clone())
+            .collect();
+
+        paths.dedup();
+        paths
+    }
+
+    /// Keep the entries in the order git does, by name and then by stage
+    pub fn sort(&mut self) {
+        self.entries.sort_by(|a, b| a.name.cmp(&b.name).then(a.flag_stage.cmp(&b.flag_stage)));
+    }
+
+    /// Read the index of the repo, a repo with nothing staged yet has no index file
+    /// so in that case we return an empty index
     pub fn read(repo: &Repository) -> Result<Self, String> {
         let index_path = Index::path(&repo);
 
-        fs::read(index_path).as_ref().and_then(|data| Ok(serde_json::from_slice(data).unwrap()))
-            .map_err(|e| e.to_string())
+        if !index_path.exists() {
+            return Ok(Index::new());
+        }
+
+        // indexes written before the binary format was used are json
+        let data = fs::read(index_path).map_err(|e| e.to_string())?;
+        if data.starts_with(INDEX_SIGNATURE) {
+            return Index::decode(&data);
+        }
+
+        serde_json::from_slice(&data).map_err(|e| e.to_string())
     }
 
     pub fn write(&self, repo: &Repository) -> Result<(), String> {
         let index_path = Index::path(&repo);
+        fs::write(index_path, self.encode()?).map_err(|e| e.to_string())
+    }
+
+    /// When the index file was last written, if there is one
+    pub fn modified(repo: &Repository) -> Option<SystemTime> {
+        fs::metadata(Index::path(repo)).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    /// Parse the binary index, see https://git-scm.com/docs/index-format. It is a header of 'DIRC', the
+    /// version and the number of entries, then the entries, then any extensions and finally the sha of
+    /// everything before it
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 32 || !data.starts_with(INDEX_SIGNATURE) {
+            return Err("Index file is truncated or not an index".to_string());
+        }
+
+        // git leaves the checksum as zeros when index.skipHash is set
+        let (contents, checksum) = data.split_at(data.len() - 20);
+        if checksum.iter().any(|byte| *byte != 0) && Sha1::digest(contents).as_slice() != checksum {
+            return Err("Index checksum does not match its contents".to_string());
+        }
+
+        let mut index = Index::new();
+        index.set_version(read_u32(contents, 4)? as i32)?;
+
+        let count = read_u32(contents, 8)?;
+        let mut offset = 12;
+        let mut previous_name = String::new();
+
+        for _ in 0..count {
+            // the count or the padding of the last entry can run past the end of a corrupt index
+            let data = contents.get(offset..).ok_or("Index file is truncated")?;
+            let (entry, size) = IndexEntry::decode(data, index.version, &previous_name)?;
+            previous_name = entry.name.clone();
+            index.entries.push(entry);
+            offset += size;
+        }
+
+        // each extension is a 4 byte signature and a 4 byte size followed by its data
+        while offset < contents.len() {
+            let signature = contents.get(offset..offset + 4).ok_or("Index extension is truncated")?;
+            let signature = String::from_utf8(signature.to_vec()).map_err(|e| e.to_string())?;
+            let size = read_u32(contents, offset + 4)? as usize;
+            let data = contents.get(offset + 8..offset + 8 + size).ok_or("Index extension is truncated")?;
 
-        // TODO: can I do this without the nested map_err?
-        serde_json::to_string(self).map_err(|e| e.to_string())
-            .and_then(|contents| fs::write(index_path, contents).map_err(|e| e.to_string()))
+            // an upper case signature marks an extension which can be ignored by tools which dont understand it
+            if !signature.starts_with(|c: char| c.is_ascii_uppercase()) {
+                return Err(format!("Unsupported index extension {}", signature));
+            }
+
+            index.extensions.push(IndexExtension { signature, data: data.to_vec() });
+            offset += 8 + size;
+        }
+
+        Ok(index)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let extended = self.entries.iter().any(|entry| entry.flag_skip_worktree || entry.flag_intent_to_add);
+        let version = if extended { self.version.max(3) } else { self.version };
+
+        let mut data = INDEX_SIGNATURE.to_vec();
+        data.extend_from_slice(&(version as u32).to_be_bytes());
+        data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+        let mut previous_name = "";
+        for entry in &self.entries {
+            entry.encode(&mut data, version, previous_name)?;
+            previous_name = &entry.name;
+        }
+
+        for extension in self.extensions.iter().filter(|extension| !CACHE_EXTENSIONS.contains(&extension.signature.as_str())) {
+            data.extend_from_slice(extension.signature.as_bytes());
+            data.extend_from_slice(&(extension.data.len() as u32).to_be_bytes());
+            data.extend_from_slice(&extension.data);
+        }
+
+        let checksum = Sha1::digest(&data);
+        data.extend_from_slice(&checksum);
+        Ok(data)
     }
 
     // TODO: should 'path' functions move to file_utils?
@@ -86,36 +259,323 @@ impl Index {
 
 impl IndexEntry {
     pub fn new(sha: String, path: String) -> Self {
-        let metadata = fs::metadata(Path::new(&path)).unwrap();
+        // the metadata of a symlink itself rather than of the file it points at
+        let metadata = fs::symlink_metadata(Path::new(&path)).unwrap();
+        let symlink = metadata.file_type().is_symlink();
+        let mtime = metadata.modified().unwrap();
 
         // For some definitions of these fields from the tutorial see https://www.gnu.org/software/libc/manual/html_node/Attribute-Meanings.html
+        let mut entry = IndexEntry {
+            time: metadata.created().unwrap_or(mtime),
+            mtime,
+            dev: 0,
+            ino: 0,
+            model_type: if symlink { ModelType::Symlink } else { ModelType::Regular },
+            model_perms: if symlink { 0 } else { 0o644 },
+            uid: 0,
+            gid: 0,
+            // for a symlink this is the length of the path it points at, which is what its blob holds
+            fsize: metadata.len(),
+            sha,
+            flag_assume_valid: false,
+            flag_stage: 0,
+            flag_skip_worktree: false,
+            flag_intent_to_add: false,
+            name: path,
+        };
+
+        #[cfg(unix)]
+        entry.set_unix_metadata(&metadata);
+
+        entry
+    }
+
+    /// Fill in the fields which only unix has, so that a file replaced by another of the same size
+    /// and mtime is still noticed. Like git we keep only the low 32 bits, which is all the index has room for
+    #[cfg(unix)]
+    fn set_unix_metadata(&mut self, metadata: &fs::Metadata) {
+        use std::os::unix::fs::MetadataExt;
+
+        self.time = UNIX_EPOCH + Duration::new(metadata.ctime() as u64, metadata.ctime_nsec() as u32);
+        self.dev = metadata.dev() as u32;
+        self.ino = metadata.ino() as u32;
+        self.uid = metadata.uid();
+        self.gid = metadata.gid();
+
+        // git only records whether a file is executable rather than its full permissions
+        if self.model_type == ModelType::Regular && metadata.mode() & 0o111 != 0 {
+            self.model_perms = 0o755;
+        }
+    }
+
+    /// The mode of the entry as written into a tree leaf e.g. 100644
+    pub fn mode(&self) -> String {
+        match self.model_type {
+            ModelType::Regular => format!("100{:o}", self.model_perms),
+            ModelType::Symlink => "120000".to_string(),
+            ModelType::Gitlink => "160000".to_string()
+        }
+    }
+
+    /// Set the type and permissions from a tree leaf mode, so an entry made from a blob keeps
+    /// the mode it had in the tree
+    pub fn set_mode(&mut self, mode: &str) {
+        (self.model_type, self.model_perms) = match mode {
+            "120000" => (ModelType::Symlink, 0),
+            "160000" => (ModelType::Gitlink, 0),
+            "100755" => (ModelType::Regular, 0o755),
+            _ => (ModelType::Regular, 0o644)
+        };
+    }
+
+    /// Create an entry for a blob which is not (yet) in the worktree. The file metadata is
+    /// zeroed so that anything comparing the entry to the worktree has to rehash the file
+    pub fn from_blob(sha: String, name: String) -> Self {
         IndexEntry {
-            time: metadata.created().unwrap(),
-            mtime: metadata.modified().unwrap(),
-            // TODO: platform specific https://doc.rust-lang.org/std/os/linux/fs/trait.MetadataExt.html#tymethod.st_dev
-            dev: "".to_string(),
-            // TODO: platform specific https://doc.rust-lang.org/std/os/linux/fs/trait.MetadataExt.html#tymethod.st_ino
+            time: SystemTime::UNIX_EPOCH,
+            mtime: SystemTime::UNIX_EPOCH,
+            dev: 0,
             ino: 0,
             model_type: ModelType::Regular,
             model_perms: 0o644,
-            // TODO: platform specific https://doc.rust-lang.org/std/os/linux/fs/trait.MetadataExt.html#tymethod.st_uid
             uid: 0,
-            // TODO: platform specific https://doc.rust-lang.org/std/os/linux/fs/trait.MetadataExt.html#tymethod.st_gid
             gid: 0,
-            fsize: metadata.len(),
+            fsize: 0,
             sha,
             flag_assume_valid: false,
-            flag_stage: false,
-            name: path,
+            flag_stage: 0,
+            flag_skip_worktree: false,
+            flag_intent_to_add: false,
+            name
         }
     }
+
+    /// Create an entry for a file in the worktree, the name is stored relative to the worktree root
+    /// so that it lines up with the paths stored in trees
+    pub fn from_worktree(repo: &Repository, sha: String, name: String) -> Self {
+        let path = Path::new(&repo.worktree).join(&name);
+        let mut entry = IndexEntry::new(sha, path.to_str().unwrap().to_string());
+        entry.name = name;
+        entry
+    }
+
+    /// Parse one binary entry from the start of the data, returning it along with the number of bytes it used.
+    /// In version 4 the path is stored relative to the path of the previous entry
+    fn decode(data: &[u8], version: i32, previous_name: &str) -> Result<(Self, usize), String> {
+        if data.len() < ENTRY_FIXED_SIZE {
+            return Err("Index entry is truncated".to_string());
+        }
+
+        let field = |n: usize| read_u32(data, n * 4);
+        let time = |n: usize| -> Result<SystemTime, String> {
+            Ok(UNIX_EPOCH + Duration::new(field(n)? as u64, field(n + 1)?))
+        };
+
+        let mode = field(6)?;
+        let model_type = match mode >> 12 {
+            0b1000 => ModelType::Regular,
+            0b1010 => ModelType::Symlink,
+            0b1110 => ModelType::Gitlink,
+            other => return Err(format!("Unknown index entry type {:b}", other))
+        };
+
+        let sha: String = data[40..60].iter().map(|byte| format!("{:02x}", byte)).collect();
+        let flags = u16::from_be_bytes([data[60], data[61]]);
+        let mut name_start = ENTRY_FIXED_SIZE;
+
+        let mut extended_flags = 0;
+        if flags & FLAG_EXTENDED != 0 {
+            if version < 3 {
+                return Err("Extended index entries need index version 3".to_string());
+            }
+            let bytes = data.get(name_start..name_start + 2).ok_or("Index entry is truncated")?;
+            extended_flags = u16::from_be_bytes([bytes[0], bytes[1]]);
+            name_start += 2;
+        }
+
+        // the path before a version 4 entry shares all but some number of its last bytes with this one
+        let mut name = Vec::new();
+        if version == 4 {
+            let (strip, length) = read_varint(&data[name_start..])?;
+            let kept = previous_name.len().checked_sub(strip).ok_or("Index entry strips more of the previous path than it has")?;
+            name.extend_from_slice(&previous_name.as_bytes()[..kept]);
+            name_start += length;
+        }
+
+        // the length in the flags is capped, so the path runs to the first nul
+        let name_end = data[name_start..].iter().position(|byte| *byte == 0)
+            .ok_or("Index entry path is not terminated")? + name_start;
+        name.extend_from_slice(&data[name_start..name_end]);
+        let name = String::from_utf8(name).map_err(|e| e.to_string())?;
+
+        let entry = IndexEntry {
+            time: time(0)?,
+            mtime: time(2)?,
+            dev: field(4)?,
+            ino: field(5)?,
+            model_type,
+            model_perms: (mode & 0o777) as i32,
+            uid: field(7)?,
+            gid: field(8)?,
+            fsize: field(9)? as u64,
+            sha,
+            flag_assume_valid: flags & FLAG_ASSUME_VALID != 0,
+            flag_stage: ((flags >> FLAG_STAGE_SHIFT) & 0b11) as u8,
+            flag_skip_worktree: extended_flags & EXTENDED_SKIP_WORKTREE != 0,
+            flag_intent_to_add: extended_flags & EXTENDED_INTENT_TO_ADD != 0,
+            name
+        };
+
+        // version 4 entries end at the nul, earlier versions are padded
+        let size = if version == 4 { name_end + 1 } else { entry_size(name_end) };
+        if size > data.len() {
+            return Err("Index entry padding runs past the end of the index".to_string());
+        }
+
+        Ok((entry, size))
+    }
+
+    fn encode(&self, data: &mut Vec<u8>, version: i32, previous_name: &str) -> Result<(), String> {
+        let start = data.len();
+        let seconds = |time: &SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let type_bits: u32 = match self.model_type {
+            ModelType::Regular => 0b1000,
+            ModelType::Symlink => 0b1010,
+            ModelType::Gitlink => 0b1110
+        };
+
+        // the fields are 32 bits wide, so like git we keep only the low bits of anything bigger
+        let fields = [
+            seconds(&self.time).as_secs() as u32, seconds(&self.time).subsec_nanos(),
+            seconds(&self.mtime).as_secs() as u32, seconds(&self.mtime).subsec_nanos(),
+            self.dev,
+            self.ino,
+            type_bits << 12 | (self.model_perms as u32 & 0o777),
+            self.uid,
+            self.gid,
+            self.fsize as u32
+        ];
+        for value in fields {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&sha_to_bytes(&self.sha)?);
+
+        let mut extended_flags = 0;
+        if self.flag_skip_worktree {
+            extended_flags |= EXTENDED_SKIP_WORKTREE;
+        }
+        if self.flag_intent_to_add {
+            extended_flags |= EXTENDED_INTENT_TO_ADD;
+        }
+
+        let mut flags = (self.name.len() as u16).min(FLAG_NAME_MASK) | (self.flag_stage as u16 & 0b11) << FLAG_STAGE_SHIFT;
+        if self.flag_assume_valid {
+            flags |= FLAG_ASSUME_VALID;
+        }
+        if extended_flags != 0 {
+            flags |= FLAG_EXTENDED;
+        }
+        data.extend_from_slice(&flags.to_be_bytes());
+        if extended_flags != 0 {
+            data.extend_from_slice(&extended_flags.to_be_bytes());
+        }
+
+        if version == 4 {
+            let shared = previous_name.bytes().zip(self.name.bytes()).take_while(|(a, b)| a == b).count();
+            write_varint(data, previous_name.len() - shared);
+            data.extend_from_slice(&self.name.as_bytes()[shared..]);
+            data.push(0);
+            return Ok(());
+        }
+
+        data.extend_from_slice(self.name.as_bytes());
+
+        // nul terminate the path and pad the entry to a multiple of 8 bytes
+        let length = data.len() - start;
+        data.resize(start + entry_size(length), 0);
+        Ok(())
+    }
+}
+
+/// The size of an entry whose fixed fields and path take up the given length, once padded with 1 to 8 nuls
+fn entry_size(length: usize) -> usize {
+    (length + 8) & !7
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or("Index file is truncated".to_string())
+}
+
+/// Read the variable length number git uses for the path prefixes of version 4, 7 bits per byte with the
+/// high bit set on all but the last. Returns the number along with how many bytes it took
+fn read_varint(data: &[u8]) -> Result<(usize, usize), String> {
+    let mut bytes = data.iter();
+    let mut byte = *bytes.next().ok_or("Index entry is truncated")?;
+    let mut value = (byte & 0x7f) as usize;
+    let mut length = 1;
+
+    // each continuation adds one before shifting, so every number has exactly one encoding
+    while byte & 0x80 != 0 {
+        byte = *bytes.next().ok_or("Index entry is truncated")?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+        length += 1;
+    }
+
+    Ok((value, length))
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: usize) {
+    let mut encoded = vec![(value & 0x7f) as u8];
+    value >>= 7;
+
+    while value != 0 {
+        value -= 1;
+        encoded.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+
+    encoded.reverse();
+    data.extend_from_slice(&encoded);
+}
+
+/// Indexes written before the binary format stored the device as a string, which was always empty
+fn deserialize_dev<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Dev {
+        Legacy(#[allow(dead_code)] String),
+        Number(u32)
+    }
+
+    match Dev::deserialize(deserializer)? {
+        Dev::Legacy(_) => Ok(0),
+        Dev::Number(dev) => Ok(dev)
+    }
+}
+
+/// Indexes written before merge support stored the stage as a bool, which was always false
+fn deserialize_stage<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stage {
+        Flag(#[allow(dead_code)] bool),
+        Number(u8)
+    }
+
+    match Stage::deserialize(deserializer)? {
+        Stage::Flag(_) => Ok(0),
+        Stage::Number(stage) => Ok(stage)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs;
     use tempdir::TempDir;
-    use crate::index::{Index, IndexEntry};
+    use std::path::Path;
+    use crate::index::{Index, IndexEntry, IndexExtension};
     use crate::repository::Repository;
 
     #[test]
@@ -133,7 +593,7 @@ mod tests {
 
         let dummy_file_path = tmp_dir.path().join("some_file.txt");
         fs::write(dummy_file_path.clone(), "dummy contents").unwrap();
-        index.add_entry(IndexEntry::new("testsha".to_string(), dummy_file_path.as_path().to_str().unwrap().into()));
+        index.add_entry(IndexEntry::new("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".to_string(), dummy_file_path.as_path().to_str().unwrap().into()));
 
         let index_write_result = index.write(&repo);
         assert!(index_write_result.is_ok());
@@ -143,4 +603,87 @@ mod tests {
 
         assert_eq!(index, index_read.unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn index_stages_and_legacy_flag() {
+        let mut index = Index::new();
+        for (name, stage) in [("b.txt", 3), ("b.txt", 1), ("a.txt", 0), ("b.txt", 2)] {
+            let mut entry = IndexEntry::from_blob("testsha".to_string(), name.to_string());
+            entry.flag_stage = stage;
+            index.add_entry(entry);
+        }
+
+        index.sort();
+        let order: Vec<(&str, u8)> = index.entries.iter().map(|entry| (entry.name.as_str(), entry.flag_stage)).collect();
+        assert_eq!(order, vec![("a.txt", 0), ("b.txt", 1), ("b.txt", 2), ("b.txt", 3)]);
+        assert_eq!(index.conflicted_paths(), vec!["b.txt"]);
+        assert_eq!(index.get_stage("b.txt", 2).map(|entry| entry.flag_stage), Some(2));
+
+        // older indexes were json which stored the stage as a bool and the device as a string
+        let legacy = serde_json::to_string(&index).unwrap()
+            .replace("\"flag_stage\":0", "\"flag_stage\":false").replace("\"dev\":0", "\"dev\":\"\"");
+        let read: Index = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(read.entries[0].flag_stage, 0);
+        assert_eq!(read.entries[3].flag_stage, 3);
+    }
+
+    #[test]
+    fn index_versions_and_extensions() {
+        // written by git as version 4 with run.sh marked skip-worktree, so it also has extended flags
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/index/v4_skip_worktree");
+        let data = fs::read(fixture).unwrap();
+        let index = Index::decode(&data).unwrap();
+
+        assert_eq!(index.version(), 4);
+        let names: Vec<&str> = index.entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["README.md", "run.sh", "src/lib.rs"]);
+        assert_eq!(index.entries[1].mode(), "100755");
+        assert_eq!(index.entries[2].sha, "432593368f9028b66c60c5f011dd54949c10ca81");
+        assert!(index.entries[1].flag_skip_worktree && !index.entries[0].flag_skip_worktree);
+        assert_eq!(index.extensions[0].signature, "TREE");
+
+        // the cached tree is dropped on writing, everything else comes out as git wrote it
+        let tree_size = 8 + index.extensions[0].data.len();
+        let encoded = index.encode().unwrap();
+        assert_eq!(encoded[..encoded.len() - 20], data[..data.len() - 20 - tree_size]);
+
+        // unknown extensions survive every version, the extended flags need at least version 3
+        let mut index = index;
+        index.extensions = vec![IndexExtension { signature: "ZTST".to_string(), data: b"kept".to_vec() }];
+        for version in 2..=4 {
+            index.set_version(version).unwrap();
+            let read = Index::decode(&index.encode().unwrap()).unwrap();
+            assert_eq!(read.version(), version.max(3));
+            assert_eq!(read.entries, index.entries);
+            assert_eq!(read.extensions, index.extensions);
+        }
+        assert!(index.set_version(5).is_err());
+
+        // a corrupted index fails its checksum, a required extension we dont know is refused
+        let mut corrupted = data.clone();
+        corrupted[20] ^= 1;
+        assert!(Index::decode(&corrupted).is_err());
+
+        // but an all zero checksum, as git writes with index.skipHash, is never checked
+        let mut unhashed = data.clone();
+        let checksum_start = unhashed.len() - 20;
+        unhashed[checksum_start..].fill(0);
+        assert_eq!(Index::decode(&unhashed).unwrap().entries, index.entries);
+
+        // entries running past the end of the file are an error rather than a panic
+        index.extensions = Vec::new();
+        index.set_version(2).unwrap();
+        let mut truncated = index.encode().unwrap();
+        truncated.truncate(truncated.len() - 24);
+        truncated.extend_from_slice(&[0; 20]);
+        assert!(Index::decode(&truncated).is_err());
+        let mut overcounted = index.encode().unwrap();
+        overcounted[11] += 1;
+        let checksum_start = overcounted.len() - 20;
+        overcounted[checksum_start..].fill(0);
+        assert!(Index::decode(&overcounted).is_err());
+
+        index.extensions = vec![IndexExtension { signature: "link".to_string(), data: Vec::new() }];
+        assert!(Index::decode(&index.encode().unwrap()).is_err());
+    }
+}
@@ -130,8 +130,6 @@ impl KeyValuePairList {
             let space_idx = input_remaining.iter().position(|&b| b == b' ');
             let newline_idx = input_remaining.iter().position(|&b| b == b'\n');
 
-            println!("space_idx {:?}, newline_idx {:?}", space_idx, newline_idx);
-
             // TODO: cleanup dupe branches
             match (space_idx, newline_idx) {
                 (None, Some(newline)) => {
@@ -157,26 +155,20 @@ impl KeyValuePairList {
                 break;
             }
 
-            let space_idx = space_idx.unwrap();
+            let space_idx = space_idx.ok_or("Key is missing its value")?;
 
             let key = input_remaining.slice(start..space_idx);
-            let key_string = String::from_utf8(key.to_vec()).unwrap();
-
-            println!("Found key string: {:?}", key_string);
+            let key_string = String::from_utf8(key.to_vec()).map_err(|e| e.to_string())?;
 
             let mut end = start;
             loop {
-                let to_search = input_remaining.slice(end+1..);
-                println!("searching {}", String::from_utf8(to_search.to_vec()).unwrap());
-
                 // find the nearest newline starting from the end of last search (but adding back the offset so that we count correctly)
-                end = input_remaining.iter().skip(end+1).position(|&b| b == b'\n').unwrap() + end + 1;
+                end = input_remaining.iter().skip(end+1).position(|&b| b == b'\n').ok_or("Value is missing its newline")? + end + 1;
 
                 if input_remaining.get(end+1) == None {
                     break;
                 }
 
-                println!("Comparing {} to {}", *input_remaining.get(end+1).unwrap(), b' ');
                 if *input_remaining.get(end+1).unwrap() != b' ' {
                     break;
                 }
@@ -185,13 +177,11 @@ impl KeyValuePairList {
             // note the end+1 in python the end is inclusive, in rust we have to make it inclusive by adding 1
             let val_to_add = input_remaining.slice(space_idx+1..end);
 
-            let formatted_val_to_add = String::from_utf8(val_to_add.to_vec()).unwrap();
+            let formatted_val_to_add = String::from_utf8(val_to_add.to_vec()).map_err(|e| e.to_string())?;
             let formatted_val_to_add = formatted_val_to_add.replace("\n ", "\n");
 
             let val_to_add = Bytes::from(formatted_val_to_add);
 
-            println!("Found value string: {:?}", val_to_add);
-
             data.insert_pair(key_string, val_to_add);
 
             start = end + 1;
@@ -252,8 +242,6 @@ impl KeyValuePairList {
                 let formatted_val_to_write = format!("{} {}\n", formatted_key, &val_to_write.replace("\n", "\n "));
                 output = format!("{}{}", output, formatted_val_to_write);
             }
-
-            println!("Output is: {}", output);
         }
 
         let contents = match &self.data.get(&KeyValuePairKey::Contents) {
@@ -8,4 +8,15 @@ pub mod key_value_list_message;
 pub mod refs;
 pub mod index;
 pub mod ignore;
-pub mod branch_utils;
\ No newline at end of file
+pub mod branch_utils;
+pub mod revision;
+pub mod checkout;
+pub mod status;
+pub mod diff;
+pub mod log;
+pub mod graphviz;
+pub mod merge;
+pub mod commit_graph;
+pub mod cherry_pick;
+pub mod rebase;
+pub mod reset;
@@ -0,0 +1,452 @@
+use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use crate::commit_graph::{commit_time, commit_walk, merge_bases, CommitOrder};
+use crate::git_object::{GitCommit, Signature};
+use crate::object_utils::{object_find, tree_to_dict};
+use crate::repository::Repository;
+use crate::revision::RevisionRange;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Which of the walked commits to show
+#[derive(Debug, Default)]
+pub struct LogOptions {
+    pub max_count: Option<usize>,
+    // A regex matched against 'name <email>' of the author
+    pub author: Option<String>,
+    // Committer timestamps, inclusive
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    // Worktree relative paths, only commits which change a file at or under one of these are shown
+    pub paths: Vec<String>
+}
+
+/// The commits reachable from the starts but not from anything hidden, in the order log shows them
+pub struct LogWalk {
+    pub commits: Vec<(String, GitCommit)>
+}
+
+/// Tracks which commit each column of the --graph output is waiting to draw
+#[derive(Debug, Default)]
+pub struct LogGraph {
+    columns: Vec<String>
+}
+
+/// Turn revision arguments into the commits to start from and the commits whose history is hidden.
+///     a..b hides everything reachable from a
+///     a...b hides everything reachable from the merge bases of a and b, leaving the symmetric difference
+///     ^a hides everything reachable from a
+pub fn log_revisions(repo: &Repository, revisions: &[String]) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+
+    for revision in revisions {
+        if let Some(excluded) = revision.strip_prefix('^') {
+            exclude.push(object_find(repo, excluded, Some("commit"), true)?);
+            continue;
+        }
+
+        match RevisionRange::parse(revision) {
+            RevisionRange::Single(name) => include.push(object_find(repo, &name, Some("commit"), true)?),
+            RevisionRange::TwoDot { exclude: excluded, include: included } => {
+                exclude.push(object_find(repo, &excluded, Some("commit"), true)?);
+                include.push(object_find(repo, &included, Some("commit"), true)?);
+            },
+            RevisionRange::ThreeDot { left, right } => {
+                let left = object_find(repo, &left, Some("commit"), true)?;
+                let right = object_find(repo, &right, Some("commit"), true)?;
+
+                exclude.extend(merge_bases(repo, &left, &right)?);
+                include.push(left);
+                include.push(right);
+            }
+        }
+    }
+
+    Ok((include, exclude))
+}
+
+impl LogWalk {
+    /// The history in the order git log --date-order shows it, a commit always before its parents
+    /// and otherwise the most recently committed first
+    pub fn new(repo: &Repository, include: &[String], exclude: &[String]) -> Result<Self, String> {
+        Ok(LogWalk { commits: commit_walk(repo, include, exclude, CommitOrder::Date)? })
+    }
+
+    /// Keep only the commits matching the options, stopping once max_count are found
+    pub fn filter(self, repo: &Repository, options: &LogOptions) -> Result<Self, String> {
+        let author = match &options.author {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| e.to_string())?),
+            None => None
+        };
+
+        let mut commits = Vec::new();
+        for (sha, commit) in self.commits {
+            if options.max_count.is_some_and(|max| commits.len() >= max) {
+                break;
+            }
+
+            let time = commit_time(&commit);
+            if options.since.is_some_and(|since| time < since) || options.until.is_some_and(|until| time > until) {
+                continue;
+            }
+
+            if let Some(author) = &author {
+                let identity = commit.author().map(|sig| sig.identity()).unwrap_or_default();
+                if !author.is_match(&identity) {
+                    continue;
+                }
+            }
+
+            if !options.paths.is_empty() && !log_touches_paths(repo, &sha, &commit, &options.paths)? {
+                continue;
+            }
+
+            commits.push((sha, commit));
+        }
+
+        Ok(LogWalk { commits })
+    }
+
+    /// Render the commits as log lines, either the full header and message or a single
+    /// 'short sha summary' line per commit, optionally with the ascii graph down the left
+    pub fn format(&self, oneline: bool, graph: bool) -> Vec<String> {
+        let shown: HashSet<&String> = self.commits.iter().map(|(sha, _)| sha).collect();
+        let mut log_graph = LogGraph::default();
+        let mut lines = Vec::new();
+
+        for (sha, commit) in &self.commits {
+            let mut text = if oneline {
+                vec![format!("{} {}", &sha[..7], commit.summary())]
+            } else {
+                log_format_full(sha, commit)
+            };
+
+            if !graph {
+                lines.append(&mut text);
+                continue;
+            }
+
+            // parents which wont be shown would leave a column open forever so they are not drawn
+            let parents: Vec<String> = commit.get_parents().into_iter().filter(|parent| shown.contains(parent)).collect();
+            let (row, prefix, connectors) = log_graph.next(sha, &parents);
+
+            for (i, line) in text.into_iter().enumerate() {
+                let lead = if i == 0 { &row } else { &prefix };
+                lines.push(format!("{} {}", lead, line).trim_end().to_string());
+            }
+            lines.extend(connectors);
+        }
+
+        lines
+    }
+}
+
+fn log_format_full(sha: &str, commit: &GitCommit) -> Vec<String> {
+    let mut lines = vec![format!("commit {}", sha)];
+
+    let parents = commit.get_parents();
+    if parents.len() > 1 {
+        let short: Vec<&str> = parents.iter().map(|parent| &parent[..7]).collect();
+        lines.push(format!("Merge: {}", short.join(" ")));
+    }
+
+    if let Some(author) = commit.author() {
+        lines.push(format!("Author: {}", author.identity()));
+        lines.push(format!("Date:   {}", format_date(&author)));
+    }
+
+    lines.push(String::new());
+    lines.extend(commit.message().lines().map(|line| format!("    {}", line)));
+    lines.push(String::new());
+
+    lines
+}
+
+/// A commit touches a path if its version of the files under that path differs from every parent,
+/// so a merge which just takes one side's version is not shown, matching git's default simplification
+fn log_touches_paths(repo: &Repository, sha: &str, commit: &GitCommit, paths: &[String]) -> Result<bool, String> {
+    let selected = |tree_sha: &str| -> Result<HashMap<String, String>, String> {
+        Ok(tree_to_dict(repo, tree_sha, None)?.into_iter()
+            .filter(|(name, _)| paths.iter().any(|path| name == path || name.starts_with(&format!("{}/", path))))
+            .collect())
+    };
+
+    let files = selected(sha)?;
+    let parents = commit.get_parents();
+
+    if parents.is_empty() {
+        return Ok(!files.is_empty());
+    }
+
+    for parent in parents {
+        if selected(&parent)? == files {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+impl LogGraph {
+    /// Place the next commit, returning its row, the prefix for any further lines of its text and any
+    /// connecting rows drawn as the columns split for a merge or join back up
+    fn next(&mut self, sha: &str, parents: &[String]) -> (String, String, Vec<String>) {
+        let idx = match self.columns.iter().position(|column| column == sha) {
+            Some(idx) => idx,
+            None => {
+                self.columns.push(sha.to_string());
+                self.columns.len() - 1
+            }
+        };
+
+        let width = self.columns.len();
+        let row = log_graph_row((0..width).map(|i| if i == idx { '*' } else { '|' }));
+
+        // The first parent continues this column. If another column is already waiting for it the two
+        // join, keeping the leftmost, and a root commit's column simply ends. Either way one column goes
+        let mut removed = match parents.first() {
+            None => Some(idx),
+            Some(first) => match self.columns.iter().position(|column| column == first) {
+                None => {
+                    self.columns[idx] = first.clone();
+                    None
+                },
+                Some(other) if other > idx => {
+                    self.columns[idx] = first.clone();
+                    Some(other)
+                },
+                Some(_) => Some(idx)
+            }
+        };
+
+        let mut added = 0;
+        for parent in parents.iter().skip(1) {
+            if !self.columns.contains(parent) {
+                added += 1;
+                self.columns.insert(idx + added, parent.clone());
+            }
+        }
+        removed = removed.map(|r| if r > idx { r + added } else { r });
+
+        let prefix = log_graph_row((0..width).map(|i| if i == idx && parents.is_empty() { ' ' } else { '|' }));
+
+        // connectors draw their slants in the gaps between columns, so column i sits at 2 * i
+        let mut connectors = Vec::new();
+        if added > 0 {
+            // the new columns branch off to the right, pushing any later columns across with them
+            let mut cells = vec![' '; 2 * width];
+            (0..=idx).for_each(|i| cells[2 * i] = '|');
+            (idx..width).for_each(|i| cells[2 * i + 1] = '\\');
+            connectors.push(cells.into_iter().collect::<String>().trim_end().to_string());
+        }
+        if let Some(r) = removed {
+            // later columns move left to fill the gap, and a joining column slants into the one on its left
+            let total = self.columns.len();
+            let mut cells = vec![' '; 2 * total];
+            (0..r).for_each(|i| cells[2 * i] = '|');
+            if r > 0 && !parents.is_empty() {
+                cells[2 * r - 1] = '/';
+            }
+            (r + 1..total).for_each(|i| cells[2 * i - 1] = '/');
+
+            if cells.contains(&'/') {
+                connectors.push(cells.into_iter().collect::<String>().trim_end().to_string());
+            }
+            self.columns.remove(r);
+        }
+
+        (row, prefix, connectors)
+    }
+}
+
+fn log_graph_row(cells: impl Iterator<Item = char>) -> String {
+    cells.map(|cell| cell.to_string()).collect::<Vec<String>>().join(" ").trim_end().to_string()
+}
+
+/// Format a commit time the way git log does e.g. 'Thu Jan 1 00:00:00 1970 +0000', in the commits own timezone
+pub fn format_date(signature: &Signature) -> String {
+    let local = signature.timestamp + signature.offset_seconds();
+    let days = local.div_euclid(86400);
+    let seconds = local.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{} {} {} {:02}:{:02}:{:02} {} {}",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60,
+        year,
+        signature.timezone
+    )
+}
+
+/// Parse a --since/--until value, either a unix timestamp or a UTC 'YYYY-MM-DD' with an optional 'HH:MM:SS'
+pub fn parse_date(value: &str) -> Result<i64, String> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(timestamp);
+    }
+
+    let invalid = || format!("Invalid date '{}', expected a timestamp or YYYY-MM-DD [HH:MM:SS]", value);
+    let numbers = |part: &str, sep: char| -> Result<Vec<i64>, String> {
+        part.split(sep).map(|n| n.parse::<i64>().map_err(|_| invalid())).collect()
+    };
+
+    let mut parts = value.splitn(2, [' ', 'T']);
+    let date = numbers(parts.next().unwrap_or(""), '-')?;
+    let time = match parts.next() {
+        Some(time) => numbers(time, ':')?,
+        None => vec![0, 0, 0]
+    };
+
+    match (date.as_slice(), time.as_slice()) {
+        ([year, month, day], [hours, minutes, seconds]) if (1..=12).contains(month) && (1..=31).contains(day) => {
+            Ok(days_from_civil(*year, *month, *day) * 86400 + hours * 3600 + minutes * 60 + seconds)
+        },
+        _ => Err(invalid())
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic gregorian date, from Howard Hinnant's date algorithms
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of days_from_civil, returning (year, month, day)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+    (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use crate::branch_utils::{branch_create, branch_switch};
+    use crate::git_object::Signature;
+    use crate::log::{format_date, log_revisions, parse_date, LogOptions, LogWalk};
+    use crate::merge::{merge, MergeOutcome};
+    use crate::repository::Repository;
+    use crate::repository::tests::commit_file;
+
+    fn summaries(walk: &LogWalk) -> Vec<String> {
+        walk.commits.iter().map(|(_, commit)| commit.summary()).collect()
+    }
+
+    #[test]
+    fn log_walk_ranges_and_filters() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        commit_file(&repo, &tmp_dir, "first.txt", "one", "First commit");
+        commit_file(&repo, &tmp_dir, "second.txt", "two", "Second commit");
+        branch_create(&repo, "feature", "HEAD", false).unwrap();
+        commit_file(&repo, &tmp_dir, "first.txt", "one again", "Third commit");
+
+        let (starts, exclude) = log_revisions(&repo, &["HEAD".to_string()]).unwrap();
+        let walk = LogWalk::new(&repo, &starts, &exclude).unwrap();
+        assert_eq!(summaries(&walk), vec!["Third commit", "Second commit", "First commit"]);
+
+        let (starts, exclude) = log_revisions(&repo, &["feature..master".to_string()]).unwrap();
+        assert_eq!(summaries(&LogWalk::new(&repo, &starts, &exclude).unwrap()), vec!["Third commit"]);
+
+        let (starts, exclude) = log_revisions(&repo, &["master".to_string(), "^HEAD~1".to_string()]).unwrap();
+        assert_eq!(summaries(&LogWalk::new(&repo, &starts, &exclude).unwrap()), vec!["Third commit"]);
+
+        let options = LogOptions { paths: vec!["first.txt".to_string()], ..Default::default() };
+        let filtered = LogWalk::new(&repo, &starts, &[]).unwrap().filter(&repo, &options).unwrap();
+        assert_eq!(summaries(&filtered), vec!["Third commit", "First commit"]);
+
+        let options = LogOptions { max_count: Some(1), author: Some("rust_git".to_string()), ..Default::default() };
+        let filtered = LogWalk::new(&repo, &starts, &[]).unwrap().filter(&repo, &options).unwrap();
+        assert_eq!(summaries(&filtered), vec!["Third commit"]);
+
+        let options = LogOptions { author: Some("someone else".to_string()), ..Default::default() };
+        assert!(LogWalk::new(&repo, &starts, &[]).unwrap().filter(&repo, &options).unwrap().commits.is_empty());
+
+        let options = LogOptions { until: Some(0), ..Default::default() };
+        assert!(LogWalk::new(&repo, &starts, &[]).unwrap().filter(&repo, &options).unwrap().commits.is_empty());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn log_graph_merge() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        let root = commit_file(&repo, &tmp_dir, "first.txt", "one", "Root");
+        branch_switch(&repo, "side", true).unwrap();
+        let side = commit_file(&repo, &tmp_dir, "side.txt", "side", "Side");
+        branch_switch(&repo, "master", false).unwrap();
+        let main = commit_file(&repo, &tmp_dir, "main.txt", "main", "Main");
+
+        let merge_sha = match merge(&repo, "side").unwrap() {
+            MergeOutcome::Merged(sha) => sha,
+            other => panic!("Expected a merge commit, got {:?}", other)
+        };
+
+        let walk = LogWalk::new(&repo, std::slice::from_ref(&merge_sha), &[]).unwrap();
+        let short = |sha: &str| sha[..7].to_string();
+
+        // commits share a timestamp here so the order of the two sides just has to stay topological
+        let lines = walk.format(true, true);
+        let expected_main_first = vec![
+            format!("* {} Merge branch 'side'", short(&merge_sha)),
+            "|\\".to_string(),
+            format!("* | {} Main", short(&main)),
+            format!("| * {} Side", short(&side)),
+            "|/".to_string(),
+            format!("* {} Root", short(&root))
+        ];
+        let expected_side_first = vec![
+            format!("* {} Merge branch 'side'", short(&merge_sha)),
+            "|\\".to_string(),
+            format!("| * {} Side", short(&side)),
+            format!("* | {} Main", short(&main)),
+            "|/".to_string(),
+            format!("* {} Root", short(&root))
+        ];
+        assert!(lines == expected_main_first || lines == expected_side_first, "{:?}", lines);
+
+        let full = walk.format(false, false);
+        assert_eq!(full[0], format!("commit {}", merge_sha));
+        assert!(full[1].starts_with("Merge: "));
+        assert_eq!(full[2], "Author: rust_git <test@example.com>");
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn log_dates() {
+        let signature = Signature::parse("a <a@b.c> 0 +0000").unwrap();
+        assert_eq!(format_date(&signature), "Thu Jan 1 00:00:00 1970 +0000");
+
+        let signature = Signature::parse("a <a@b.c> 1700000000 -0130").unwrap();
+        assert_eq!(format_date(&signature), "Tue Nov 14 20:43:20 2023 -0130");
+
+        assert_eq!(parse_date("2023-11-14 22:13:20"), Ok(1700000000));
+        assert_eq!(parse_date("1970-01-02"), Ok(86400));
+        assert_eq!(parse_date("1700000000"), Ok(1700000000));
+        assert!(parse_date("yesterday").is_err());
+    }
+}
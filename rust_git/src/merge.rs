@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use bytes::Bytes;
+use crate::branch_utils::head_advance;
+use crate::checkout::{checkout_tree, checkout_write_blob, restore_paths};
+use crate::cherry_pick::pick_in_progress;
+use crate::diff::{diff_lines, is_binary, Edit};
+use crate::commit_graph::merge_base;
+use crate::file_utils::repo_file;
+use crate::git_object::{GitBlob, GitObject, GitWriteable};
+use crate::index::{Index, IndexEntry};
+use crate::object_utils::{object_find, object_read, object_write, tree_to_entries};
+use crate::rebase::rebase_in_progress;
+use crate::refs::Ref;
+use crate::repository::Repository;
+use crate::status::Status;
+
+/// The commit being merged in while a conflicted merge waits to be committed
+const MERGE_HEAD: &str = "MERGE_HEAD";
+/// The message the merge commit will get if none is given
+pub const MERGE_MSG: &str = "MERGE_MSG";
+
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    // The other commit is already part of our history
+    UpToDate,
+    // Our history is part of the other commit so the branch just moves forward to it
+    FastForward(String),
+    // A merge commit was created
+    Merged(String),
+    // The merge stopped with these paths conflicted, to be resolved and committed by hand
+    Conflicts(Vec<String>)
+}
+
+/// The result of merging a single path
+#[derive(Debug, PartialEq)]
+pub enum TreeMergeEntry {
+    // The merged blob sha and mode
+    Clean(String, String),
+    // The three (sha, mode) versions are kept for the index stages, the worktree gets the conflict
+    // markers or, for a file deleted on one side, whatever survived on the other
+    Conflict {
+        base: Option<(String, String)>,
+        ours: Option<(String, String)>,
+        theirs: Option<(String, String)>,
+        worktree: Vec<u8>,
+        message: String
+    }
+}
+
+/// A three-way merge of the lines of a file, returning the merged contents and whether there were conflicts.
+///
+/// This is diff3: both sides are diffed against the base and the lines of the base which survive
+/// unchanged in both split the files into stable and unstable chunks. An unstable chunk changed on
+/// only one side takes that side, if both sides made the same change it is taken once, and anything
+/// else is a conflict written between '<<<<<<<', '=======' and '>>>>>>>' markers
+pub fn merge_lines(base: &[u8], ours: &[u8], theirs: &[u8], ours_label: &str, theirs_label: &str) -> (Vec<u8>, bool) {
+    let base_text = String::from_utf8_lossy(base);
+    let ours_text = String::from_utf8_lossy(ours);
+    let theirs_text = String::from_utf8_lossy(theirs);
+    let base_lines: Vec<&str> = base_text.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours_text.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs_text.split_inclusive('\n').collect();
+
+    let matches = |other: &[&str]| -> HashMap<usize, usize> {
+        diff_lines(&base_lines, other).into_iter()
+            .filter_map(|edit| match edit {
+                Edit::Equal(b, o) => Some((b, o)),
+                _ => None
+            })
+            .collect()
+    };
+    let ours_matches = matches(&ours_lines);
+    let theirs_matches = matches(&theirs_lines);
+
+    let mut out = String::new();
+    let mut conflict = false;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+
+    loop {
+        // copy the lines unchanged on both sides
+        let mut stable = 0;
+        while i + stable < base_lines.len()
+            && ours_matches.get(&(i + stable)) == Some(&(j + stable))
+            && theirs_matches.get(&(i + stable)) == Some(&(k + stable)) {
+            out.push_str(base_lines[i + stable]);
+            stable += 1;
+        }
+        i += stable;
+        j += stable;
+        k += stable;
+
+        if i == base_lines.len() && j == ours_lines.len() && k == theirs_lines.len() {
+            break;
+        }
+
+        // the unstable chunk runs up to the next base line which both sides kept, or to the end
+        let next = (i..base_lines.len()).find(|b| ours_matches.contains_key(b) && theirs_matches.contains_key(b));
+        let (base_end, ours_end, theirs_end) = match next {
+            Some(b) => (b, ours_matches[&b], theirs_matches[&b]),
+            None => (base_lines.len(), ours_lines.len(), theirs_lines.len())
+        };
+
+        let base_chunk = &base_lines[i..base_end];
+        let ours_chunk = &ours_lines[j..ours_end];
+        let theirs_chunk = &theirs_lines[k..theirs_end];
+
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            theirs_chunk.iter().for_each(|line| out.push_str(line));
+        } else if theirs_chunk == base_chunk {
+            ours_chunk.iter().for_each(|line| out.push_str(line));
+        } else {
+            conflict = true;
+            out.push_str(&format!("<<<<<<< {}\n", ours_label));
+            merge_push_chunk(&mut out, ours_chunk);
+            out.push_str("=======\n");
+            merge_push_chunk(&mut out, theirs_chunk);
+            out.push_str(&format!(">>>>>>> {}\n", theirs_label));
+        }
+
+        i = base_end;
+        j = ours_end;
+        k = theirs_end;
+    }
+
+    (out.into_bytes(), conflict)
+}
+
+/// Lines inside conflict markers always need a newline, even the last line of a file without one
+fn merge_push_chunk(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+}
+
+/// Merge the files of three trees, each a map of path to blob sha and mode as from tree_to_entries.
+///
+/// A path changed on only one side takes that side's version (including deleting it or changing
+/// its mode). When both
+/// sides changed a path differently the contents are merged line by line, which can conflict, and
+/// a path deleted on one side but modified on the other is always a conflict. Renames are not detected
+pub fn merge_trees(repo: &Repository, base: &HashMap<String, (String, String)>, ours: &HashMap<String, (String, String)>,
+                   theirs: &HashMap<String, (String, String)>, ours_label: &str, theirs_label: &str) -> Result<BTreeMap<String, TreeMergeEntry>, String> {
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    let mut result = BTreeMap::new();
+
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+
+        let merged = if o == t || b == t {
+            o.map(|(sha, mode)| TreeMergeEntry::Clean(sha.clone(), mode.clone()))
+        } else if b == o {
+            t.map(|(sha, mode)| TreeMergeEntry::Clean(sha.clone(), mode.clone()))
+        } else {
+            Some(match (o, t) {
+                (Some(o), Some(t)) => merge_file(repo, path, b, o, t, ours_label, theirs_label)?,
+                (Some(o), None) => TreeMergeEntry::Conflict {
+                    base: b.cloned(),
+                    ours: Some(o.clone()),
+                    theirs: None,
+                    worktree: merge_read_blob(repo, &o.0)?,
+                    message: format!("CONFLICT (modify/delete): {} deleted in {} and modified in {}", path, theirs_label, ours_label)
+                },
+                (None, Some(t)) => TreeMergeEntry::Conflict {
+                    base: b.cloned(),
+                    ours: None,
+                    theirs: Some(t.clone()),
+                    worktree: merge_read_blob(repo, &t.0)?,
+                    message: format!("CONFLICT (modify/delete): {} deleted in {} and modified in {}", path, ours_label, theirs_label)
+                },
+                (None, None) => unreachable!("both sides deleting a path is handled as the same change")
+            })
+        };
+
+        if let Some(entry) = merged {
+            result.insert(path.clone(), entry);
+        }
+    }
+
+    Ok(result)
+}
+
+fn merge_file(repo: &Repository, path: &str, base: Option<&(String, String)>, ours: &(String, String), theirs: &(String, String),
+              ours_label: &str, theirs_label: &str) -> Result<TreeMergeEntry, String> {
+    let base_data = match base {
+        Some((sha, _)) => merge_read_blob(repo, sha)?,
+        None => Vec::new()
+    };
+    let ours_data = merge_read_blob(repo, &ours.0)?;
+    let theirs_data = merge_read_blob(repo, &theirs.0)?;
+
+    // a mode change on only their side is kept, otherwise the file keeps our mode
+    let mode = match base {
+        Some((_, base_mode)) if base_mode == &ours.1 => theirs.1.clone(),
+        _ => ours.1.clone()
+    };
+
+    let kind = if base.is_some() { "content" } else { "add/add" };
+    let conflict = |worktree: Vec<u8>, message: String| TreeMergeEntry::Conflict {
+        base: base.cloned(),
+        ours: Some(ours.clone()),
+        theirs: Some(theirs.clone()),
+        worktree,
+        message
+    };
+
+    if is_binary(&base_data) || is_binary(&ours_data) || is_binary(&theirs_data) {
+        return Ok(conflict(ours_data, format!("CONFLICT ({}): Binary files differ in {}, keeping {}", kind, path, ours_label)));
+    }
+
+    match merge_lines(&base_data, &ours_data, &theirs_data, ours_label, theirs_label) {
+        (merged, true) => Ok(conflict(merged, format!("CONFLICT ({}): Merge conflict in {}", kind, path))),
+        (merged, false) => {
+            let sha = object_write(GitObject::Blob(GitBlob::deserialize(Bytes::from(merged))), Some(repo))?;
+            Ok(TreeMergeEntry::Clean(sha, mode))
+        }
+    }
+}
+
+fn merge_read_blob(repo: &Repository, sha: &str) -> Result<Vec<u8>, String> {
+    match object_read(repo, sha.to_string())? {
+        GitObject::Blob(blob) => Ok(blob.data.map(|data| data.to_vec()).unwrap_or_default()),
+        other => Err(format!("Expected {} to be a blob but found a {}", sha, other.format_name()))
+    }
+}
+
+/// Merge another branch or commit into HEAD.
+///
+/// If HEAD already contains the commit there is nothing to do, and if the commit contains HEAD the
+/// branch is fast-forwarded to it. Otherwise the trees are merged against the merge base and a merge
+/// commit is made, unless there were conflicts in which case the merge is left in progress: the
+/// conflicted paths have stages 1-3 in the index and markers in the worktree, and committing once they
+/// are resolved and added finishes the merge
+pub fn merge(repo: &Repository, name: &str) -> Result<MergeOutcome, String> {
+    if merge_head_read(repo).is_some() {
+        return Err("You have not concluded your merge (MERGE_HEAD exists), commit or abort it first".to_string());
+    }
+
+    if pick_in_progress(repo).is_some() {
+        return Err("A cherry-pick or revert is in progress, continue or abort it first".to_string());
+    }
+
+    if rebase_in_progress(repo) {
+        return Err("A rebase is in progress, continue or abort it first".to_string());
+    }
+
+    let head = object_find(repo, "HEAD", Some("commit"), true)?;
+    let theirs = object_find(repo, name, Some("commit"), true)?;
+    let base = merge_base(repo, &head, &theirs)?;
+
+    if base.as_ref() == Some(&theirs) {
+        return Ok(MergeOutcome::UpToDate);
+    }
+
+    let status = Status::read(repo)?;
+    if !status.staged.is_empty() || !status.unstaged.is_empty() {
+        return Err("Your local changes would be overwritten by merge, commit them first".to_string());
+    }
+
+    if base.as_ref() == Some(&head) {
+        checkout_tree(repo, &theirs)?;
+        head_advance(repo, &theirs)?;
+        return Ok(MergeOutcome::FastForward(theirs));
+    }
+
+    let base_tree = match &base {
+        Some(sha) => tree_to_entries(repo, sha)?,
+        None => HashMap::new()
+    };
+    let ours_tree = tree_to_entries(repo, &head)?;
+    let theirs_tree = tree_to_entries(repo, &theirs)?;
+
+    let result = merge_trees(repo, &base_tree, &ours_tree, &theirs_tree, "HEAD", name)?;
+    let conflicts = merge_apply(repo, &ours_tree, &result)?;
+
+    let message = if Ref::new(format!("refs/heads/{}", name)).exists(repo) {
+        format!("Merge branch '{}'", name)
+    } else {
+        format!("Merge commit '{}'", name)
+    };
+
+    merge_state_write(repo, &theirs, &message)?;
+
+    if conflicts.is_empty() {
+        repo.commit(message).map(MergeOutcome::Merged)
+    } else {
+        Ok(MergeOutcome::Conflicts(conflicts))
+    }
+}
+
+/// Write the merged tree into the index and worktree, returning the conflicted paths. Paths which
+/// are unchanged from our side keep their index entries as they are
+pub fn merge_apply(repo: &Repository, ours_tree: &HashMap<String, (String, String)>, result: &BTreeMap<String, TreeMergeEntry>) -> Result<Vec<String>, String> {
+    // an untracked file in the way of a path the merge creates would be lost
+    let blocked: Vec<&String> = result.keys()
+        .filter(|path| !ours_tree.contains_key(*path) && Path::new(&repo.worktree).join(path).exists())
+        .collect();
+
+    if !blocked.is_empty() {
+        let names: Vec<&str> = blocked.iter().map(|path| path.as_str()).collect();
+        return Err(format!("The following untracked files would be overwritten by merge:\n\t{}", names.join("\n\t")));
+    }
+
+    let old_index = Index::read(repo)?;
+    let mut index = Index::new();
+    let mut conflicts = Vec::new();
+
+    for path in ours_tree.keys().filter(|path| !result.contains_key(*path)) {
+        println!("Removing {}", path);
+        repo.delete_worktree_file(path)?;
+    }
+
+    for (path, entry) in result {
+        match entry {
+            TreeMergeEntry::Clean(sha, mode) if ours_tree.get(path) == Some(&(sha.clone(), mode.clone())) => {
+                let existing = old_index.get_entry(path).cloned();
+                index.add_entry(existing.unwrap_or_else(|| merge_stage_entry(sha, mode, path, 0)));
+            },
+            TreeMergeEntry::Clean(sha, mode) => {
+                println!("Updating {}", path);
+                checkout_write_blob(repo, sha, path, Some(mode))?;
+                index.add_entry(IndexEntry::from_worktree(repo, sha.clone(), path.clone()));
+            },
+            TreeMergeEntry::Conflict { base, ours, theirs, worktree, message } => {
+                println!("{}", message);
+                let full_path = Path::new(&repo.worktree).join(path);
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(full_path, worktree).map_err(|e| e.to_string())?;
+
+                for (stage, version) in [(1, base), (2, ours), (3, theirs)] {
+                    if let Some((sha, mode)) = version {
+                        index.add_entry(merge_stage_entry(sha, mode, path, stage));
+                    }
+                }
+
+                conflicts.push(path.clone());
+            }
+        }
+    }
+
+    index.sort();
+    index.write(repo)?;
+
+    Ok(conflicts)
+}
+
+fn merge_stage_entry(sha: &str, mode: &str, path: &str, stage: u8) -> IndexEntry {
+    let mut entry = IndexEntry::from_blob(sha.to_string(), path.to_string());
+    entry.set_mode(mode);
+    entry.flag_stage = stage;
+    entry
+}
+
+/// Throw away an in progress merge, putting the index and worktree back to HEAD
+pub fn merge_abort(repo: &Repository) -> Result<(), String> {
+    if merge_head_read(repo).is_none() {
+        return Err("There is no merge to abort (MERGE_HEAD missing)".to_string());
+    }
+
+    restore_paths(repo, vec![repo.worktree.clone()], true, true, Some("HEAD"))?;
+    merge_state_clear(repo)
+}
+
+/// The sha of the commit being merged, if a merge is in progress
+pub fn merge_head_read(repo: &Repository) -> Option<String> {
+    repo_file(repo, vec![MERGE_HEAD.to_string()], false).ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|contents| contents.trim().to_string())
+}
+
+/// The prepared message of an in progress merge
+pub fn merge_message_read(repo: &Repository) -> Option<String> {
+    repo_file(repo, vec![MERGE_MSG.to_string()], false).ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+}
+
+fn merge_state_write(repo: &Repository, theirs: &str, message: &str) -> Result<(), String> {
+    repo_file(repo, vec![MERGE_HEAD.to_string()], false)
+        .and_then(|path| fs::write(path, format!("{}\n", theirs)).map_err(|e| e.to_string()))?;
+    repo_file(repo, vec![MERGE_MSG.to_string()], false)
+        .and_then(|path| fs::write(path, message).map_err(|e| e.to_string()))
+}
+
+/// Remove the merge state files, called once the merge commit is made or the merge is aborted
+pub fn merge_state_clear(repo: &Repository) -> Result<(), String> {
+    for name in [MERGE_HEAD, MERGE_MSG] {
+        let path = Path::new(&repo_file(repo, vec![name.to_string()], false)?).to_owned();
+        if path.exists() {
+            fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::branch_utils::{branch_create, branch_switch};
+    use crate::checkout::checkout_in_place;
+    use crate::commit_graph::{commit_read, merge_base};
+    use crate::index::Index;
+    use crate::merge::{merge, merge_abort, merge_head_read, merge_lines, MergeOutcome};
+    use crate::object_utils::object_find;
+    use crate::repository::Repository;
+    use crate::repository::tests::commit_file;
+    use crate::status::{Status, Unmerged};
+
+    #[test]
+    fn merge_lines_clean_and_conflicting() {
+        let base = b"one\ntwo\nthree\nfour\nfive\n";
+
+        // changes to different lines combine
+        let ours = b"ONE\ntwo\nthree\nfour\nfive\n";
+        let theirs = b"one\ntwo\nthree\nfour\nFIVE\nsix\n";
+        let (merged, conflict) = merge_lines(base, ours, theirs, "HEAD", "feature");
+        assert!(!conflict);
+        assert_eq!(String::from_utf8(merged).unwrap(), "ONE\ntwo\nthree\nfour\nFIVE\nsix\n");
+
+        // the same change on both sides is taken once
+        let (merged, conflict) = merge_lines(base, ours, ours, "HEAD", "feature");
+        assert!(!conflict);
+        assert_eq!(merged, ours.to_vec());
+
+        // different changes to the same line conflict
+        let theirs = b"uno\ntwo\nthree\nfour\nfive\n";
+        let (merged, conflict) = merge_lines(base, ours, theirs, "HEAD", "feature");
+        assert!(conflict);
+        assert_eq!(String::from_utf8(merged).unwrap(), "<<<<<<< HEAD\nONE\n=======\nuno\n>>>>>>> feature\ntwo\nthree\nfour\nfive\n");
+
+        // add/add with no base and no trailing newline
+        let (merged, conflict) = merge_lines(b"", b"a", b"b", "HEAD", "feature");
+        assert!(conflict);
+        assert_eq!(String::from_utf8(merged).unwrap(), "<<<<<<< HEAD\na\n=======\nb\n>>>>>>> feature\n");
+    }
+
+    #[test]
+    fn merge_fast_forward_and_clean_merge() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        let root = commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\nthree\n", "Root");
+        branch_create(&repo, "feature", "HEAD", false).unwrap();
+        checkout_in_place(&repo, "feature").unwrap();
+        let feature = commit_file(&repo, &tmp_dir, "feature.txt", "feature", "Feature");
+
+        checkout_in_place(&repo, "master").unwrap();
+        assert_eq!(merge_base(&repo, &root, &feature), Ok(Some(root.clone())));
+        assert_eq!(merge(&repo, "feature"), Ok(MergeOutcome::FastForward(feature.clone())));
+        assert_eq!(object_find(&repo, "master", None, true), Ok(feature.clone()));
+        assert!(tmp_dir.path().join("feature.txt").exists());
+        assert_eq!(merge(&repo, "feature"), Ok(MergeOutcome::UpToDate));
+
+        // diverge, changing different lines of the shared file
+        let main = commit_file(&repo, &tmp_dir, "shared.txt", "ONE\ntwo\nthree\n", "Main");
+        checkout_in_place(&repo, "feature").unwrap();
+        let side = commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\nTHREE\n", "Side");
+        checkout_in_place(&repo, "master").unwrap();
+
+        assert_eq!(merge_base(&repo, &main, &side), Ok(Some(feature.clone())));
+        let merged = match merge(&repo, "feature").unwrap() {
+            MergeOutcome::Merged(sha) => sha,
+            other => panic!("Expected a merge commit, got {:?}", other)
+        };
+
+        let commit = commit_read(&repo, &merged).unwrap();
+        assert_eq!(commit.get_parents(), vec![main, side]);
+        assert_eq!(commit.message(), "Merge branch 'feature'");
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "ONE\ntwo\nTHREE\n");
+        assert_eq!(merge_head_read(&repo), None);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn merge_conflicts_resolve_and_abort() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\n", "Root");
+        commit_file(&repo, &tmp_dir, "deleted.txt", "keep me", "Add a file");
+        branch_switch(&repo, "feature", true).unwrap();
+        commit_file(&repo, &tmp_dir, "shared.txt", "theirs\n", "Side");
+        repo.rm(vec![tmp_dir.path().join("deleted.txt").to_str().unwrap().to_string()], true, false, false).unwrap();
+        let side = repo.commit("Delete a file".to_string()).unwrap();
+
+        checkout_in_place(&repo, "master").unwrap();
+        commit_file(&repo, &tmp_dir, "shared.txt", "ours\n", "Main");
+        let main = commit_file(&repo, &tmp_dir, "deleted.txt", "changed", "Change the deleted file");
+
+        assert_eq!(merge(&repo, "feature"), Ok(MergeOutcome::Conflicts(vec!["deleted.txt".to_string(), "shared.txt".to_string()])));
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> feature\n");
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("deleted.txt")).unwrap(), "changed");
+        assert_eq!(merge_head_read(&repo), Some(side.clone()));
+
+        let index = Index::read(&repo).unwrap();
+        let stages: Vec<u8> = index.entries.iter().filter(|entry| entry.name == "shared.txt").map(|entry| entry.flag_stage).collect();
+        assert_eq!(stages, vec![1, 2, 3]);
+        assert!(index.get_stage("deleted.txt", 3).is_none());
+
+        let status = Status::read(&repo).unwrap();
+        assert_eq!(status.unmerged, vec![(Unmerged::DeletedByThem, "deleted.txt".to_string()), (Unmerged::BothModified, "shared.txt".to_string())]);
+        assert!(status.staged.is_empty());
+
+        // committing is refused until the conflicts are resolved
+        assert!(repo.commit("Merge".to_string()).unwrap_err().starts_with("Committing is not possible"));
+        assert!(merge(&repo, "feature").is_err());
+
+        merge_abort(&repo).unwrap();
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "ours\n");
+        assert!(Index::read(&repo).unwrap().conflicted_paths().is_empty());
+        assert_eq!(merge_head_read(&repo), None);
+
+        // merging again and resolving by hand gives a merge commit
+        merge(&repo, "feature").unwrap();
+        fs::write(tmp_dir.path().join("shared.txt"), "both\n").unwrap();
+        repo.add(vec![tmp_dir.path().to_str().unwrap().to_string()]).unwrap();
+        let merged = repo.commit("Merge branch 'feature'".to_string()).unwrap();
+
+        assert_eq!(commit_read(&repo, &merged).unwrap().get_parents(), vec![main, side]);
+        assert_eq!(merge_head_read(&repo), None);
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -1,14 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io::{Read, Write};
 use std::num::ParseIntError;
 use std::path::{Path, PathBuf};
 use bytes::{BufMut, Bytes, BytesMut};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
 use regex::Regex;
 use sha1::{Digest, Sha1};
-use crate::file_utils::{repo_dir, repo_file};
-use crate::git_object::{GitBlob, GitCommit, GitObject, GitTag, GitTree, GitWriteable};
+use crate::config::TreeFormat;
+use crate::file_utils::{repo_dir, repo_file, repo_path};
+use crate::git_object::{GitBlob, GitCommit, GitLeaf, GitObject, GitTag, GitTree, GitWriteable, TREE_MODE};
+use crate::index::{Index, IndexEntry};
 use crate::refs::{Ref, RefType};
 use crate::repository::Repository;
+use crate::revision::revision_resolve;
 
 /// Parse a git object given the sha hash of the file
 ///
@@ -16,9 +23,11 @@ use crate::repository::Repository;
 /// - the sha hash is split into [first 2 chars]:[rest of chars]
 /// - the file lives inside .git/[first 2 chars]/[rest of chars]
 ///
-/// Once loaded the contents of the file follows the pattern:
+/// Once loaded and inflated the contents of the file follows the pattern:
 /// [format][space char][object size][null byte][data]
 ///
+/// Objects written before compression was added are stored as is, see object_inflate.
+///
 /// Where format is one of the types of GitObject e.g. Commit, Blob etc.
 ///
 /// Once the type is determined we can deserialize the data into an instance of GitObject
@@ -32,7 +41,7 @@ pub fn object_read(repo: &Repository, sha: String) -> Result<GitObject, String>
     }
 
     let bytes_raw = fs::read(path).map_err(|e| e.to_string())?;
-    let bytes = Bytes::from(bytes_raw);
+    let bytes = Bytes::from(object_inflate(bytes_raw)?);
 
     let format_loc_index = bytes.iter().position(|&b| b == b' ').ok_or("Couldnt locate format locator byte")?;
     let format = &bytes.as_ref()[..format_loc_index];
@@ -56,7 +65,11 @@ pub fn object_read(repo: &Repository, sha: String) -> Result<GitObject, String>
     match std::str::from_utf8(format) {
         Ok("blob") => Ok(GitObject::Blob(GitBlob::deserialize(Bytes::from(data.to_owned())))),
         Ok("commit") => Ok(GitObject::Commit(GitCommit::deserialize(Bytes::from(data.to_owned())))),
-        Ok("tree") => Ok(GitObject::Tree(GitTree::deserialize(Bytes::from(data.to_owned())))),
+        // trees and tags on disk may be corrupt, so decode them through the fallible paths rather than the trait
+        Ok("tree") if data.first() == Some(&b'{') => Ok(GitObject::Tree(serde_json::from_slice(data).map_err(|e| e.to_string())?)),
+        Ok("tree") => Ok(GitObject::Tree(GitTree::deserialize_binary(data)?)),
+        Ok("tag") if data.first() == Some(&b'{') => Ok(GitObject::Tag(serde_json::from_slice(data).map_err(|e| e.to_string())?)),
+        Ok("tag") => Ok(GitObject::Tag(GitTag::deserialize_git(Bytes::from(data.to_owned()))?)),
         Ok(other) => {
             // TODO: work out how to get the 'other' string into the Err message without issues of 'value referencing data owned by the current function'
             println!("ERROR: unable to parse format: {}", other);
@@ -71,7 +84,8 @@ pub fn object_read(repo: &Repository, sha: String) -> Result<GitObject, String>
 /// The object follows the pattern:
 /// [format][space char][object size][null byte][data]
 ///
-/// Can be undone via the object_read function
+/// The sha is of these uncompressed bytes, but like git they are zlib deflated on disk at the
+/// level set in the repo config. Can be undone via the object_read function
 pub fn object_write(obj: GitObject, repo_option: Option<&Repository>) -> Result<String, String> {
     // TODO: I could definitely have done this more nicely, in particular by actioning the other TODO in git_object.rs
     //       about not having the 'inner types' of the GitObject enum and directly implementing the below traits
@@ -79,16 +93,17 @@ pub fn object_write(obj: GitObject, repo_option: Option<&Repository>) -> Result<
     let data = match &obj {
         GitObject::Blob(blob) => blob.serialize(),
         GitObject::Commit(commit) => commit.serialize(),
-        GitObject::Tree(tree) => tree.serialize(),
-        GitObject::Tag(tag) => tag.serialize(),
+        GitObject::Tree(tree) => match repo_option.map(|repo| repo.conf.tree_format()) {
+            Some(TreeFormat::Binary) => tree.serialize_binary()?,
+            _ => tree.serialize()
+        },
+        GitObject::Tag(tag) => match repo_option {
+            Some(repo) if repo.conf.git_compatible() => tag.serialize_git()?,
+            _ => tag.serialize()
+        },
     };
 
-    let format = match &obj  {
-        GitObject::Blob(_) => GitBlob::format_name(),
-        GitObject::Commit(_) => GitCommit::format_name(),
-        GitObject::Tree(_) => GitTree::format_name(),
-        GitObject::Tag(_) => GitTag::format_name(),
-    };
+    let format = obj.format_name();
 
     // todo: if we reserve with BytesMut::with_capacity(n) upfront we get better efficiency
     let mut output_data = BytesMut::new();
@@ -107,7 +122,8 @@ pub fn object_write(obj: GitObject, repo_option: Option<&Repository>) -> Result<
         let path_obj = Path::new(&path);
 
         if !path_obj.exists() {
-            fs::write(path, output_data).or_else(|e| Err(e.to_string()))?;
+            let compressed = object_deflate(&output_data, repo.conf.compression_level()?)?;
+            fs::write(path, compressed).map_err(|e| e.to_string())?;
         }
 
     }
@@ -115,35 +131,110 @@ pub fn object_write(obj: GitObject, repo_option: Option<&Repository>) -> Result<
     Ok(sha)
 }
 
-// TODO: format is allowed to be blank, make it optional?
-pub fn object_find(repo: &Repository, name: &String, format: &String, follow: bool) -> String {
-    println!("Running object_find for {:?}, {} {} {}", repo, name, format, follow);
-    name.to_owned()
+fn object_deflate(data: &[u8], level: u32) -> Result<Vec<u8>, String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(data).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+/// Inflate the contents of an object file, or return them unchanged if they were written before
+/// objects were compressed. The two can be told apart by the first two bytes: a zlib stream starts
+/// with a header whose first byte is 0x78 for the window size git uses, and whose two bytes together
+/// are a multiple of 31, while an uncompressed object starts with the ascii name of its format
+fn object_inflate(raw: Vec<u8>) -> Result<Vec<u8>, String> {
+    let is_zlib = raw.len() >= 2 && raw[0] == 0x78 && (u16::from(raw[0]) << 8 | u16::from(raw[1])) % 31 == 0;
+    if !is_zlib {
+        return Ok(raw);
+    }
+
+    let mut data = Vec::new();
+    ZlibDecoder::new(raw.as_slice()).read_to_end(&mut data).map_err(|e| format!("Corrupt object: {}", e))?;
+    Ok(data)
+}
+
+/// Resolve a revision to the sha of exactly one object
+///
+/// The revision is parsed by the revision module so it can be a plain name such as HEAD, a
+/// branch, tag or remote name, a full ref such as refs/heads/main or a 4-40 char hex prefix of a
+/// sha, followed by any ancestry or peel operators e.g. HEAD~2, v1.0^{tree} or main:src/lib.rs
+///
+/// If a format is given we check the object is of that type, and when follow is true we peel
+/// tags to the object they point at and commits to their tree until we reach that format
+pub fn object_find(repo: &Repository, name: &str, format: Option<&str>, follow: bool) -> Result<String, String> {
+    let sha = revision_resolve(repo, name)?;
+
+    match format {
+        None => Ok(sha),
+        Some(format) if follow => object_peel(repo, sha, format),
+        Some(format) => {
+            let obj_format = object_read(repo, sha.clone())?.format_name();
+            if obj_format == format {
+                Ok(sha)
+            } else {
+                Err(format!("{} is a {} not a {}", name, obj_format, format))
+            }
+        }
+    }
+}
+
+/// Resolve a plain name (no revision operators) to a single sha, refs take priority
+/// over short hashes as they do in git
+pub fn object_resolve_name(repo: &Repository, name: &str) -> Result<String, String> {
+    let candidates = object_resolve(repo, name)?;
+
+    match candidates.len() {
+        0 => Err(format!("No such reference {}", name)),
+        1 => Ok(candidates[0].clone()),
+        _ => Err(format!("Ambiguous reference {}, candidates are:\n - {}", name, candidates.join("\n - ")))
+    }
+}
+
+/// Starting from an object, peel tags to the object they point at and commits
+/// to their tree until we reach an object of the requested format
+pub fn object_peel(repo: &Repository, sha: String, format: &str) -> Result<String, String> {
+    let mut sha = sha;
+
+    loop {
+        let obj = object_read(repo, sha.clone())?;
+        let obj_format = obj.format_name();
+
+        if obj_format == format {
+            return Ok(sha);
+        }
+
+        sha = match obj {
+            GitObject::Tag(tag) => tag.target(),
+            GitObject::Commit(commit) if format == "tree" => commit.get_tree_string()
+                .ok_or(format!("Commit {} has no tree", sha))?,
+            _ => return Err(format!("{} is a {} and cannot be peeled to a {}", sha, obj_format, format))
+        };
+    }
 }
 
 /// A watered down version of the full git resolution algorithm
 ///
-/// Including support for 'short hashes', to reference a hash by the first 6 chars of the hash
+/// Including support for 'short hashes', to reference a hash by the first 4 or more chars of the hash
 ///
 /// From the object_find chapter: https://wyag.thb.lt/#object_find
-fn object_resolve(repo: &Repository, name: String) -> Vec<String> {
+fn object_resolve(repo: &Repository, name: &str) -> Result<Vec<String>, String> {
     let mut candidates = Vec::new();
 
     let trimmed = name.trim().to_owned();
     if trimmed.is_empty() {
-        return candidates;
+        return Ok(candidates);
     }
 
     if trimmed == "HEAD" {
-        let resolved = Ref::new("HEAD".to_string()).fully_resolve(&repo);
-
-        let head_ref = match resolved {
-            RefType::Direct(head_ref) => head_ref,
-            _ => panic!("Couldnt resolve ref to head")
+        return match Ref::new("HEAD".to_string()).fully_resolve(repo) {
+            RefType::Direct(head_ref) => Ok(vec![head_ref]),
+            _ => Err("HEAD does not point to a commit yet".to_string())
         };
+    }
 
-        candidates.push(head_ref);
-        return candidates;
+    // Refs are searched in the same order as git, taking the first that exists
+    if let Some(sha) = ref_resolve(repo, &trimmed) {
+        candidates.push(sha);
+        return Ok(candidates);
     }
 
     // If its a hex string, try to resolve
@@ -152,31 +243,114 @@ fn object_resolve(repo: &Repository, name: String) -> Vec<String> {
         let name = trimmed.to_lowercase();
         let (prefix, rem) = name.split_at(2);
 
-        let path = repo_dir(&repo, vec!["objects".to_string(), prefix.to_string()], false);
-
-        if path.is_ok() {
-            for path in fs::read_dir(path.unwrap()).unwrap() {
-                let path_str = path.unwrap().file_name().to_str().unwrap().to_string();
-                if path_str.starts_with(rem) {
-                    candidates.push(format!("{}{}", prefix, path_str));
+        if let Ok(path) = repo_dir(repo, vec!["objects".to_string(), prefix.to_string()], false) {
+            if let Ok(entries) = fs::read_dir(path) {
+                for entry in entries.filter_map(|entry| entry.ok()) {
+                    let path_str = entry.file_name().to_str().unwrap().to_string();
+                    if path_str.starts_with(rem) {
+                        candidates.push(format!("{}{}", prefix, path_str));
+                    }
                 }
             }
         }
     }
 
-    // todo https://wyag.thb.lt/#org38de95c
-    todo!("Implement reference resolution");
+    candidates.sort();
+    candidates.dedup();
+    Ok(candidates)
+}
+
+/// Find the first of the places git looks for a ref with the given name which exists,
+/// returning the sha it resolves to
+fn ref_resolve(repo: &Repository, name: &str) -> Option<String> {
+    let mut ref_names = Vec::new();
+
+    // Only all caps names such as ORIG_HEAD are looked up directly in the gitdir, else
+    // we would try and parse files like the config as refs
+    if name.starts_with("refs/") || name.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+        ref_names.push(name.to_string());
+    }
+
+    ref_names.push(format!("refs/{}", name));
+    ref_names.push(format!("refs/tags/{}", name));
+    ref_names.push(format!("refs/heads/{}", name));
+    ref_names.push(format!("refs/remotes/{}", name));
+    ref_names.push(format!("refs/remotes/{}/HEAD", name));
+
+    for ref_name in ref_names {
+        let path = repo_path(repo, vec![ref_name.clone()]);
+        if !Path::new(&path).is_file() {
+            continue;
+        }
+
+        if let RefType::Direct(sha) = Ref::new(ref_name).fully_resolve(repo) {
+            return Some(sha);
+        }
+    }
+
+    None
+}
+
+/// Turn the flat list of index entries into nested trees, writing one subtree per directory,
+/// and return the sha of the root tree
+pub fn tree_from_index(repo: &Repository, index: &Index) -> Result<String, String> {
+    let entries: Vec<(&str, &IndexEntry)> = index.entries.iter()
+        .map(|entry| (entry.name.as_str(), entry))
+        .collect();
 
-    candidates
+    tree_write_entries(repo, &entries)
 }
 
-pub fn tree_to_dict(repo: &Repository, name: &String, prefix: Option<&'static str>) -> HashMap<String, String> {
+/// Write a tree for a set of entries whose paths are relative to that tree, recursing for
+/// anything in a subdirectory so that the subtree sha can be added as a leaf
+fn tree_write_entries(repo: &Repository, entries: &[(&str, &IndexEntry)]) -> Result<String, String> {
+    let mut tree = GitTree::new();
+    let mut subdirs: BTreeMap<&str, Vec<(&str, &IndexEntry)>> = BTreeMap::new();
+
+    for (path, entry) in entries {
+        match path.split_once('/') {
+            Some((dir, rest)) => subdirs.entry(dir).or_default().push((rest, entry)),
+            None => tree.add(GitLeaf::new(entry.mode(), path.to_string(), entry.sha.clone()))
+        }
+    }
+
+    for (dir, subdir_entries) in subdirs {
+        let sha = tree_write_entries(repo, &subdir_entries)?;
+        tree.add(GitLeaf::new(TREE_MODE.to_string(), dir.to_string(), sha));
+    }
+
+    object_write(GitObject::Tree(tree), Some(repo))
+}
+
+/// Flatten a tree into a map of full 'dir/sub/file' paths to blob shas, recursing into any subtrees
+pub fn tree_to_dict(repo: &Repository, name: &str, prefix: Option<&str>) -> Result<HashMap<String, String>, String> {
+    Ok(tree_to_leaves(repo, name, prefix)?.into_iter().map(|(path, leaf)| (path, leaf.sha)).collect())
+}
+
+/// Flatten a tree into a map of full paths to the modes of their blobs, e.g. 100755 for an executable
+pub fn tree_to_modes(repo: &Repository, name: &str) -> Result<HashMap<String, String>, String> {
+    Ok(tree_to_leaves(repo, name, None)?.into_iter().map(|(path, leaf)| (path, leaf.mode)).collect())
+}
+
+/// Flatten a tree into a map of full paths to the sha and mode of their blobs
+pub fn tree_to_entries(repo: &Repository, name: &str) -> Result<HashMap<String, (String, String)>, String> {
+    Ok(tree_to_leaves(repo, name, None)?.into_iter().map(|(path, leaf)| (path, (leaf.sha, leaf.mode))).collect())
+}
+
+/// The leaves of a tree by full path. A HEAD with no commits yet is the empty tree, anything else
+/// which is missing or unreadable is an error, as callers delete whatever the tree does not list
+fn tree_to_leaves(repo: &Repository, name: &str, prefix: Option<&str>) -> Result<HashMap<String, GitLeaf>, String> {
     let mut ret = HashMap::new();
 
-    let tree_sha = object_find(&repo, name, &"tree".to_string(), true);
-    let tree = match object_read(&repo, tree_sha) {
-        Ok(GitObject::Tree(obj)) => obj,
-        _ => return ret
+    let tree_sha = match object_find(repo, name, Some("tree"), true) {
+        Ok(sha) => sha,
+        Err(_) if name == "HEAD" && Ref::new("HEAD".to_string()).fully_resolve(repo) == RefType::Broken => return Ok(ret),
+        Err(e) => return Err(e)
+    };
+
+    let tree = match object_read(repo, tree_sha.clone())? {
+        GitObject::Tree(obj) => obj,
+        other => return Err(format!("Expected {} to be a tree but found a {}", tree_sha, other.format_name()))
     };
 
     for leaf in tree.items {
@@ -191,10 +365,163 @@ pub fn tree_to_dict(repo: &Repository, name: &String, prefix: Option<&'static st
             path.push(item);
         }
 
-        // TODO: add subtree support per here https://wyag.thb.lt/#org154db98
+        let path_str = path.as_path().to_str().unwrap().to_string();
+
+        if leaf.is_tree() {
+            ret.extend(tree_to_leaves(repo, &leaf.sha, Some(&path_str))?);
+        } else {
+            ret.insert(path_str, leaf);
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use std::collections::HashMap;
+    use bytes::Bytes;
+    use crate::object_utils::{object_find, object_read, object_write, tree_to_dict};
+    use crate::git_object::{GitBlob, GitObject, GitWriteable};
+    use crate::repository::Repository;
+
+    #[test]
+    fn object_compression() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let mut repo = Repository::create(tmp_dir_string).unwrap();
+        let object_path = |sha: &str| tmp_dir.path().join(".git").join("objects").join(&sha[..2]).join(&sha[2..]);
+
+        let data = "generated asset line\n".repeat(200);
+        let blob = |data: &str| GitObject::Blob(GitBlob::deserialize(Bytes::from(data.to_string())));
+
+        // deflated on disk but hashed and read back as the raw object
+        let sha = object_write(blob(&data), Some(&repo)).unwrap();
+        assert_eq!(sha, object_write(blob(&data), None).unwrap());
+        let stored = fs::read(object_path(&sha)).unwrap();
+        assert_eq!(stored[0], 0x78);
+        assert!(stored.len() < data.len() / 10);
+        match object_read(&repo, sha.clone()).unwrap() {
+            GitObject::Blob(read) => assert_eq!(read.serialize(), Bytes::from(data.clone())),
+            other => panic!("Expected a blob, got {:?}", other)
+        }
+
+        // level 0 stores the data without shrinking it
+        repo.conf.contents.core.compression = Some(0);
+        let sha = object_write(blob("stored uncompressed"), Some(&repo)).unwrap();
+        assert!(fs::read(object_path(&sha)).unwrap().len() > "blob 19\0stored uncompressed".len());
+
+        repo.conf.contents.core.compression = Some(12);
+        assert!(object_write(blob("bad level"), Some(&repo)).is_err());
+
+        // objects written before compression are still read
+        let legacy = object_write(blob("legacy object"), None).unwrap();
+        fs::create_dir_all(object_path(&legacy).parent().unwrap()).unwrap();
+        fs::write(object_path(&legacy), "blob 13\0legacy object").unwrap();
+        match object_read(&repo, legacy).unwrap() {
+            GitObject::Blob(read) => assert_eq!(read.serialize(), Bytes::from("legacy object")),
+            other => panic!("Expected a blob, got {:?}", other)
+        }
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn object_read_corrupt_object() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+        let sha = "ab".repeat(20);
+        let object_path = tmp_dir.path().join(".git").join("objects").join(&sha[..2]).join(&sha[2..]);
+        fs::create_dir_all(object_path.parent().unwrap()).unwrap();
+
+        // both encodings give an error rather than a panic
+        fs::write(&object_path, "tree 9\0{\"items\":").unwrap();
+        assert!(object_read(&repo, sha.clone()).is_err());
+        fs::write(&object_path, [&b"tree 16\0"[..], b"100644 truncated"].concat()).unwrap();
+        assert!(object_read(&repo, sha.clone()).is_err());
+
+        fs::write(&object_path, "tag 9\0{\"data\":").unwrap();
+        assert!(object_read(&repo, sha.clone()).is_err());
+        fs::write(&object_path, "tag 13\0object abcdef").unwrap();
+        assert!(object_read(&repo, sha.clone()).is_err());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn object_find_names() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        // nothing is committed yet so HEAD cant be resolved
+        assert!(object_find(&repo, "HEAD", None, true).is_err());
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let commit_sha = repo.commit("First commit".to_string()).unwrap();
+
+        assert_eq!(object_find(&repo, "HEAD", None, true), Ok(commit_sha.clone()));
+        assert_eq!(object_find(&repo, "master", None, true), Ok(commit_sha.clone()));
+        assert_eq!(object_find(&repo, "refs/heads/master", Some("commit"), true), Ok(commit_sha.clone()));
+        assert_eq!(object_find(&repo, &commit_sha[..6], None, true), Ok(commit_sha.clone()));
+        assert_eq!(object_find(&repo, &commit_sha.to_uppercase(), None, true), Ok(commit_sha.clone()));
+        assert!(object_find(&repo, "not_a_branch", None, true).is_err());
+
+        // follow peels the commit to its tree, without follow the format has to match exactly
+        let tree_sha = object_find(&repo, "master", Some("tree"), true).unwrap();
+        assert!(matches!(object_read(&repo, tree_sha).unwrap(), GitObject::Tree(_)));
+        assert!(object_find(&repo, "master", Some("tree"), false).is_err());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn object_find_ambiguous_short_hash() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        // resolution only looks at file names so we can fake two objects sharing a prefix
+        let object_dir = tmp_dir.path().join(".git/objects/ab");
+        fs::create_dir_all(&object_dir).unwrap();
+        fs::write(object_dir.join("cd1111111111111111111111111111111111111"), "").unwrap();
+        fs::write(object_dir.join("cd2222222222222222222222222222222222222"), "").unwrap();
 
-        ret.insert(path.as_path().to_str().unwrap().into(), leaf.sha);
+        let result = object_find(&repo, "abcd", None, true);
+        assert!(result.unwrap_err().starts_with("Ambiguous reference abcd"));
+        assert_eq!(object_find(&repo, "abcd1", None, true), Ok("abcd1111111111111111111111111111111111111".to_string()));
+
+        tmp_dir.close().unwrap();
     }
 
-    ret
-}
\ No newline at end of file
+    #[test]
+    fn tree_to_dict_nested() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::create_dir_all(tmp_dir.path().join("dir/sub")).unwrap();
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("dir/second.txt"), "second file").unwrap();
+        fs::write(tmp_dir.path().join("dir/sub/third.txt"), "third file").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        repo.commit("First commit".to_string()).unwrap();
+
+        let expected: HashMap<String, String> = ["first.txt", "dir/second.txt", "dir/sub/third.txt"].iter()
+            .map(|name| (name.to_string(), repo.hash_worktree_file(name).unwrap().unwrap()))
+            .collect();
+
+        assert_eq!(tree_to_dict(&repo, "HEAD", None).unwrap(), expected);
+
+        // the prefix is prepended to every path
+        let prefixed = tree_to_dict(&repo, "HEAD", Some("root")).unwrap();
+        assert_eq!(prefixed.get("root/dir/sub/third.txt"), expected.get("dir/sub/third.txt"));
+        assert_eq!(prefixed.len(), 3);
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,502 @@
+use std::fs;
+use crate::branch_utils::{branch_get_active, branch_write, head_set_branch, head_set_detached};
+use crate::checkout::{checkout_tree, restore_paths};
+use crate::cherry_pick::{pick_apply, pick_in_progress, PickAction};
+use crate::commit_graph::{commit_read, commit_walk, is_ancestor, CommitOrder};
+use crate::file_utils::{repo_dir, repo_file};
+use crate::git_object::{GitCommit, GitObject};
+use crate::index::Index;
+use crate::merge::merge_head_read;
+use crate::object_utils::{object_find, object_write, tree_from_index};
+use crate::repository::Repository;
+use crate::status::Status;
+
+/// Everything needed to continue or abort a stopped rebase lives in this dir of the gitdir
+const REBASE_DIR: &str = "rebase-merge";
+/// What HEAD pointed at before the rebase, a branch ref or 'detached HEAD'
+const HEAD_NAME: &str = "head-name";
+const ORIG_HEAD: &str = "orig-head";
+const ONTO: &str = "onto";
+/// The steps still to run, one per line
+const TODO: &str = "git-rebase-todo";
+/// The steps already run, the last being the one stopped at when there are conflicts
+const DONE: &str = "done";
+/// The last commit the rebase made, the only one a squash or fixup may fold into
+const LAST_COMMIT: &str = "last-commit";
+const DETACHED: &str = "detached HEAD";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RebaseCommand {
+    // Replay the commit as it is
+    Pick,
+    // Replay the commit with a new message, the rest of the todo line
+    Reword(String),
+    // Fold the commit into the previous one, joining the messages
+    Squash,
+    // Fold the commit into the previous one, keeping only the previous message
+    Fixup,
+    // Leave the commit out
+    Drop
+}
+
+/// A line of the todo list: a command and the full sha of the commit it applies to
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebaseStep {
+    pub command: RebaseCommand,
+    pub sha: String
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RebaseOutcome {
+    // The branch already contains the upstream, there is nothing to replay
+    UpToDate,
+    // Every step ran and the branch now points at this commit
+    Finished(String),
+    // A step stopped with these paths conflicted, to be resolved, added and continued
+    Conflicts(Vec<String>)
+}
+
+impl RebaseStep {
+    /// Parse a todo line such as 'pick 1a2b3c4 summary', where the commit can be any revision. Anything
+    /// after the commit is ignored except for reword, which takes it as the new message as there is no
+    /// editor to ask for one. Blank lines and '#' comments give None
+    pub fn parse(repo: &Repository, line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let command = parts.next().unwrap_or_default();
+        let name = parts.next().ok_or(format!("Missing commit in rebase todo line '{}'", line))?;
+        let rest = parts.next().unwrap_or_default().trim();
+
+        let command = match command {
+            "pick" | "p" => RebaseCommand::Pick,
+            "reword" | "r" => RebaseCommand::Reword(rest.to_string()),
+            "squash" | "s" => RebaseCommand::Squash,
+            "fixup" | "f" => RebaseCommand::Fixup,
+            "drop" | "d" => RebaseCommand::Drop,
+            other => return Err(format!("Unknown rebase command '{}'", other))
+        };
+
+        let sha = object_find(repo, name, Some("commit"), true)?;
+        Ok(Some(RebaseStep { command, sha }))
+    }
+
+    fn to_line(&self) -> String {
+        match &self.command {
+            RebaseCommand::Pick => format!("pick {}", self.sha),
+            RebaseCommand::Reword(message) => format!("reword {} {}", self.sha, message),
+            RebaseCommand::Squash => format!("squash {}", self.sha),
+            RebaseCommand::Fixup => format!("fixup {}", self.sha),
+            RebaseCommand::Drop => format!("drop {}", self.sha)
+        }
+    }
+}
+
+/// Parse a whole todo list, checking that squashes and fixups have something to fold into
+pub fn rebase_todo_parse(repo: &Repository, text: &str) -> Result<Vec<RebaseStep>, String> {
+    let mut steps = Vec::new();
+
+    for line in text.lines() {
+        if let Some(step) = RebaseStep::parse(repo, line)? {
+            let folds = matches!(step.command, RebaseCommand::Squash | RebaseCommand::Fixup);
+            if folds && !steps.iter().any(|step: &RebaseStep| step.command != RebaseCommand::Drop) {
+                return Err(format!("Cannot '{}' without a previous commit", step.to_line()));
+            }
+            steps.push(step);
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Replay the commits of HEAD which are not in the upstream on top of it, then move the branch to the
+/// result. Without a todo list each of those commits is picked in order, oldest first, leaving out merges.
+///
+/// HEAD is detached at the upstream while the steps run. If a step conflicts the rebase stops with
+/// the conflicts in the index and worktree, to be resolved, added and continued, or aborted
+pub fn rebase(repo: &Repository, upstream: &str, todo: Option<Vec<RebaseStep>>) -> Result<RebaseOutcome, String> {
+    if rebase_in_progress(repo) {
+        return Err("A rebase is already in progress, continue or abort it first".to_string());
+    }
+
+    if merge_head_read(repo).is_some() || pick_in_progress(repo).is_some() {
+        return Err("A merge, cherry-pick or revert is in progress, finish or abort it first".to_string());
+    }
+
+    let status = Status::read(repo)?;
+    if !status.staged.is_empty() || !status.unstaged.is_empty() {
+        return Err("Cannot rebase: You have unstaged or staged changes, commit them first".to_string());
+    }
+
+    let head = object_find(repo, "HEAD", Some("commit"), true)?;
+    let onto = object_find(repo, upstream, Some("commit"), true)?;
+
+    let steps = match todo {
+        Some(steps) => steps,
+        None if is_ancestor(repo, &onto, &head)? => return Ok(RebaseOutcome::UpToDate),
+        None => commit_walk(repo, std::slice::from_ref(&head), std::slice::from_ref(&onto), CommitOrder::Topo)?.into_iter().rev()
+            .filter(|(_, commit)| commit.get_parents().len() < 2)
+            .map(|(sha, _)| RebaseStep { command: RebaseCommand::Pick, sha })
+            .collect()
+    };
+
+    let head_name = branch_get_active(repo).map(|branch| format!("refs/heads/{}", branch)).unwrap_or(DETACHED.to_string());
+    rebase_state_write(repo, HEAD_NAME, &head_name)?;
+    rebase_state_write(repo, ORIG_HEAD, &head)?;
+    rebase_state_write(repo, ONTO, &onto)?;
+    rebase_state_write(repo, DONE, "")?;
+    rebase_state_write(repo, LAST_COMMIT, "")?;
+    rebase_todo_write(repo, &steps)?;
+
+    checkout_tree(repo, &onto)?;
+    head_set_detached(repo, &onto)?;
+
+    rebase_run(repo)
+}
+
+/// Finish the step a rebase stopped at once its conflicts are resolved and added, then carry on
+pub fn rebase_continue(repo: &Repository) -> Result<RebaseOutcome, String> {
+    if !rebase_in_progress(repo) {
+        return Err("No rebase in progress".to_string());
+    }
+
+    let conflicts = Index::read(repo)?.conflicted_paths();
+    if !conflicts.is_empty() {
+        return Err(format!("You must resolve and add the conflicted files before continuing:\n\t{}", conflicts.join("\n\t")));
+    }
+
+    let done = rebase_state_read(repo, DONE)?;
+    if let Some(line) = done.lines().last() {
+        if let Some(step) = RebaseStep::parse(repo, line)? {
+            rebase_commit_step(repo, &step)?;
+        }
+    }
+
+    rebase_run(repo)
+}
+
+/// Throw away a rebase, putting the index, worktree and HEAD back to how they were before it started
+pub fn rebase_abort(repo: &Repository) -> Result<(), String> {
+    if !rebase_in_progress(repo) {
+        return Err("No rebase in progress".to_string());
+    }
+
+    let head_name = rebase_state_read(repo, HEAD_NAME)?;
+    let orig_head = rebase_state_read(repo, ORIG_HEAD)?;
+
+    // clear any conflicts first so checking out the original commit only sees a clean worktree
+    restore_paths(repo, vec![repo.worktree.clone()], true, true, Some("HEAD"))?;
+    checkout_tree(repo, &orig_head)?;
+
+    match head_name.strip_prefix("refs/heads/") {
+        Some(branch) => head_set_branch(repo, branch)?,
+        None => head_set_detached(repo, &orig_head)?
+    }
+
+    rebase_state_clear(repo)
+}
+
+pub fn rebase_in_progress(repo: &Repository) -> bool {
+    repo_file(repo, vec![REBASE_DIR.to_string(), HEAD_NAME.to_string()], false)
+        .is_ok_and(|path| fs::metadata(path).is_ok())
+}
+
+/// Run the remaining steps of the todo list, stopping at the first conflict
+fn rebase_run(repo: &Repository) -> Result<RebaseOutcome, String> {
+    loop {
+        let mut steps = rebase_todo_parse_state(repo)?;
+        if steps.is_empty() {
+            return rebase_finish(repo);
+        }
+
+        let step = steps.remove(0);
+        rebase_todo_write(repo, &steps)?;
+        let mut done = rebase_state_read(repo, DONE)?;
+        done.push_str(&format!("{}\n", step.to_line()));
+        rebase_state_write(repo, DONE, &done)?;
+
+        if step.command == RebaseCommand::Drop {
+            continue;
+        }
+
+        let commit = commit_read(repo, &step.sha)?;
+        let conflicts = pick_apply(repo, &step.sha, &commit, PickAction::CherryPick)?;
+        if !conflicts.is_empty() {
+            return Ok(RebaseOutcome::Conflicts(conflicts));
+        }
+
+        rebase_commit_step(repo, &step)?;
+    }
+}
+
+/// Commit the applied change of a step on top of HEAD, or in place of HEAD for a squash or fixup.
+/// A picked commit whose changes are already upstream leaves nothing to commit and is skipped.
+///
+/// A squash or fixup only folds into a commit this rebase made, if the steps before it were all
+/// skipped HEAD is still the upstream which must not be rewritten, so it is picked instead
+fn rebase_commit_step(repo: &Repository, step: &RebaseStep) -> Result<(), String> {
+    let tree = tree_from_index(repo, &Index::read(repo)?)?;
+    let head = object_find(repo, "HEAD", Some("commit"), true)?;
+    let head_commit = commit_read(repo, &head)?;
+    let commit = commit_read(repo, &step.sha)?;
+    let author = |commit: &GitCommit| commit.author().map(|author| author.to_string()).unwrap_or_else(|| repo.conf.signature());
+    let folds = rebase_state_read(repo, LAST_COMMIT)?.trim() == head;
+
+    let (parents, author, message) = match &step.command {
+        RebaseCommand::Drop => return Ok(()),
+        RebaseCommand::Squash if folds => (head_commit.get_parents(), author(&head_commit), format!("{}\n\n{}", head_commit.message(), commit.message())),
+        RebaseCommand::Fixup if folds => (head_commit.get_parents(), author(&head_commit), head_commit.message()),
+        _ if head_commit.get_tree_string().as_ref() == Some(&tree) => {
+            println!("Skipping {} as its changes are already applied", &step.sha[..7]);
+            return Ok(());
+        },
+        RebaseCommand::Reword(message) if !message.is_empty() => (vec![head], author(&commit), message.clone()),
+        _ => (vec![head], author(&commit), commit.message())
+    };
+
+    let rewritten = GitCommit::create_with_committer(tree, parents, author, repo.conf.signature(), message);
+    let sha = object_write(GitObject::Commit(rewritten), Some(repo))?;
+    rebase_state_write(repo, LAST_COMMIT, &sha)?;
+    head_set_detached(repo, &sha)
+}
+
+/// Move the branch being rebased to the rewritten history and put HEAD back on it
+fn rebase_finish(repo: &Repository) -> Result<RebaseOutcome, String> {
+    let head = object_find(repo, "HEAD", Some("commit"), true)?;
+
+    if let Some(branch) = rebase_state_read(repo, HEAD_NAME)?.strip_prefix("refs/heads/") {
+        branch_write(repo, branch, &head)?;
+        head_set_branch(repo, branch)?;
+    }
+
+    rebase_state_clear(repo)?;
+    Ok(RebaseOutcome::Finished(head))
+}
+
+/// The remaining steps, which can start with a squash or fixup of a commit already replayed
+fn rebase_todo_parse_state(repo: &Repository) -> Result<Vec<RebaseStep>, String> {
+    let mut steps = Vec::new();
+    for line in rebase_state_read(repo, TODO)?.lines() {
+        steps.extend(RebaseStep::parse(repo, line)?);
+    }
+
+    Ok(steps)
+}
+
+fn rebase_todo_write(repo: &Repository, steps: &[RebaseStep]) -> Result<(), String> {
+    let lines: Vec<String> = steps.iter().map(|step| format!("{}\n", step.to_line())).collect();
+    rebase_state_write(repo, TODO, &lines.concat())
+}
+
+fn rebase_state_read(repo: &Repository, name: &str) -> Result<String, String> {
+    repo_file(repo, vec![REBASE_DIR.to_string(), name.to_string()], false)
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+}
+
+fn rebase_state_write(repo: &Repository, name: &str, contents: &str) -> Result<(), String> {
+    repo_file(repo, vec![REBASE_DIR.to_string(), name.to_string()], true)
+        .and_then(|path| fs::write(path, contents).map_err(|e| e.to_string()))
+}
+
+pub fn rebase_state_clear(repo: &Repository) -> Result<(), String> {
+    let path = repo_dir(repo, vec![REBASE_DIR.to_string()], false)?;
+    fs::remove_dir_all(path).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::branch_utils::{branch_get_active, branch_switch};
+    use crate::checkout::checkout_in_place;
+    use crate::cherry_pick::cherry_pick;
+    use crate::commit_graph::{commit_read, commit_walk, CommitOrder};
+    use crate::merge::merge;
+    use crate::object_utils::object_find;
+    use crate::rebase::{rebase, rebase_abort, rebase_continue, rebase_in_progress, rebase_todo_parse, RebaseOutcome};
+    use crate::repository::Repository;
+    use crate::repository::tests::commit_file;
+
+    fn messages(repo: &Repository, tip: &str, base: &str) -> Vec<String> {
+        commit_walk(repo, &[tip.to_string()], &[base.to_string()], CommitOrder::Topo).unwrap().into_iter()
+            .map(|(_, commit)| commit.message())
+            .collect()
+    }
+
+    #[test]
+    fn rebase_replays_onto_upstream() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\nthree\n", "Root");
+        branch_switch(&repo, "feature", true).unwrap();
+        commit_file(&repo, &tmp_dir, "feature.txt", "feature", "Add feature");
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\nTHREE\n", "Change three");
+
+        checkout_in_place(&repo, "master").unwrap();
+        let master = commit_file(&repo, &tmp_dir, "shared.txt", "ONE\ntwo\nthree\n", "Change one");
+
+        checkout_in_place(&repo, "feature").unwrap();
+        let rebased = match rebase(&repo, "master", None).unwrap() {
+            RebaseOutcome::Finished(sha) => sha,
+            other => panic!("Expected the rebase to finish, got {:?}", other)
+        };
+
+        assert_eq!(object_find(&repo, "feature", None, true), Ok(rebased.clone()));
+        assert_eq!(branch_get_active(&repo), Some("feature".to_string()));
+        assert_eq!(messages(&repo, &rebased, &master), vec!["Change three", "Add feature"]);
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "ONE\ntwo\nTHREE\n");
+        assert!(!rebase_in_progress(&repo));
+
+        assert_eq!(rebase(&repo, "master", None), Ok(RebaseOutcome::UpToDate));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn rebase_todo_commands() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        let root = commit_file(&repo, &tmp_dir, "root.txt", "root", "Root");
+        let shas: Vec<String> = ["a", "b", "c", "d", "e"].iter()
+            .map(|name| commit_file(&repo, &tmp_dir, &format!("{}.txt", name), name, &format!("Add {}", name)))
+            .collect();
+
+        let todo = format!("# rewrite the last five commits\npick {}\nsquash {}\nfixup {}\n\ndrop {}\nreword {} Add e, reworded\n",
+                           shas[0], shas[1], shas[2], shas[3], &shas[4][..7]);
+        let steps = rebase_todo_parse(&repo, &todo).unwrap();
+        assert_eq!(steps.len(), 5);
+        assert!(rebase_todo_parse(&repo, &format!("fixup {}", shas[0])).is_err());
+        assert!(rebase_todo_parse(&repo, &format!("edit {}", shas[0])).is_err());
+
+        let rebased = match rebase(&repo, &root, Some(steps)).unwrap() {
+            RebaseOutcome::Finished(sha) => sha,
+            other => panic!("Expected the rebase to finish, got {:?}", other)
+        };
+
+        assert_eq!(messages(&repo, &rebased, &root), vec!["Add e, reworded", "Add a\n\nAdd b"]);
+        assert_eq!(commit_read(&repo, &rebased).unwrap().get_parents().len(), 1);
+        for (name, exists) in [("a", true), ("b", true), ("c", true), ("d", false), ("e", true)] {
+            assert_eq!(tmp_dir.path().join(format!("{}.txt", name)).exists(), exists, "{}.txt", name);
+        }
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn rebase_conflicts_continue_and_abort() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\n", "Root");
+        branch_switch(&repo, "feature", true).unwrap();
+        commit_file(&repo, &tmp_dir, "shared.txt", "feature\n", "Feature change");
+        let feature = commit_file(&repo, &tmp_dir, "other.txt", "other", "Other file");
+        checkout_in_place(&repo, "master").unwrap();
+        let master = commit_file(&repo, &tmp_dir, "shared.txt", "master\n", "Master change");
+        checkout_in_place(&repo, "feature").unwrap();
+
+        assert_eq!(rebase(&repo, "master", None), Ok(RebaseOutcome::Conflicts(vec!["shared.txt".to_string()])));
+        assert!(rebase_in_progress(&repo));
+        assert_eq!(branch_get_active(&repo), None);
+        assert!(rebase(&repo, "master", None).is_err());
+        assert!(rebase_continue(&repo).is_err());
+        assert!(merge(&repo, &feature).is_err());
+        assert!(cherry_pick(&repo, &feature).is_err());
+
+        rebase_abort(&repo).unwrap();
+        assert!(!rebase_in_progress(&repo));
+        assert_eq!(branch_get_active(&repo), Some("feature".to_string()));
+        assert_eq!(object_find(&repo, "HEAD", None, true), Ok(feature));
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "feature\n");
+
+        rebase(&repo, "master", None).unwrap();
+        fs::write(tmp_dir.path().join("shared.txt"), "master and feature\n").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        let rebased = match rebase_continue(&repo).unwrap() {
+            RebaseOutcome::Finished(sha) => sha,
+            other => panic!("Expected the rebase to finish, got {:?}", other)
+        };
+
+        assert_eq!(messages(&repo, &rebased, &master), vec!["Other file", "Feature change"]);
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "master and feature\n");
+        assert!(tmp_dir.path().join("other.txt").exists());
+        assert_eq!(object_find(&repo, "feature", None, true), Ok(rebased));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn rebase_squash_after_skipped_pick() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        commit_file(&repo, &tmp_dir, "root.txt", "root", "Root");
+        branch_switch(&repo, "feature", true).unwrap();
+        let applied = commit_file(&repo, &tmp_dir, "a.txt", "a", "Add a");
+        let squashed = commit_file(&repo, &tmp_dir, "b.txt", "b", "Add b");
+        checkout_in_place(&repo, "master").unwrap();
+        let master = commit_file(&repo, &tmp_dir, "a.txt", "a", "Add a upstream");
+        checkout_in_place(&repo, "feature").unwrap();
+
+        // the pick is skipped so the squash has no commit of the rebase to fold into and is picked instead
+        let steps = rebase_todo_parse(&repo, &format!("pick {}\nsquash {}", applied, squashed)).unwrap();
+        let rebased = match rebase(&repo, "master", Some(steps)).unwrap() {
+            RebaseOutcome::Finished(sha) => sha,
+            other => panic!("Expected the rebase to finish, got {:?}", other)
+        };
+
+        assert_eq!(messages(&repo, &rebased, &master), vec!["Add b"]);
+        assert_eq!(commit_read(&repo, &rebased).unwrap().get_parents(), vec![master]);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rebase_keeps_modes() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        use crate::object_utils::tree_to_modes;
+
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\ntwo\n", "Root");
+        branch_switch(&repo, "feature", true).unwrap();
+        let script = tmp_dir.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(tmp_dir.path().join("shared.txt"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("run.sh", tmp_dir.path().join("link")).unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        repo.commit("Script and link".to_string()).unwrap();
+
+        checkout_in_place(&repo, "master").unwrap();
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\nTWO\n", "Change two");
+        checkout_in_place(&repo, "feature").unwrap();
+
+        let rebased = match rebase(&repo, "master", None).unwrap() {
+            RebaseOutcome::Finished(sha) => sha,
+            other => panic!("Expected the rebase to finish, got {:?}", other)
+        };
+
+        // the mode change on shared.txt survives merging with the content change from master
+        let modes = tree_to_modes(&repo, &rebased).unwrap();
+        assert_eq!(modes.get("run.sh").map(|mode| mode.as_str()), Some("100755"));
+        assert_eq!(modes.get("shared.txt").map(|mode| mode.as_str()), Some("100755"));
+        assert_eq!(modes.get("link").map(|mode| mode.as_str()), Some("120000"));
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o111, 0o111);
+        assert_eq!(fs::read_link(tmp_dir.path().join("link")).unwrap().to_str(), Some("run.sh"));
+
+        tmp_dir.close().unwrap();
+    }
+}
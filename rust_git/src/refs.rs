@@ -2,7 +2,7 @@ use std::fs;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
-use crate::file_utils::{repo_dir, repo_file};
+use crate::file_utils::{repo_dir, repo_file, repo_path};
 use crate::repository::Repository;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,7 +41,9 @@ impl Ref {
     // TODO: this should return a Result<Ref, String> and make the error handling cleaner
     pub fn from_file(name: String, repo: &Repository) -> Self {
         let path = repo_file(repo, vec![name.clone()], false).map_err(|e| e.to_string()).unwrap();
-        fs::read(path).and_then(|data| Ok(serde_json::from_slice(&data).unwrap())).unwrap()
+        let data = fs::read_to_string(path).unwrap();
+        // take the name from the path, the json refs carry one too but the plain text refs of git dont
+        Ref { name, target: Some(Ref::parse_target(&data)) }
     }
 
     pub fn all_refs(repo: &Repository) -> Vec<Ref> {
@@ -93,11 +95,48 @@ impl Ref {
             return Err("Cant write a file without a ref".to_string());
         }
 
-        let path = repo_file(repo, vec![self.name.clone()], false).map_err(|e| e.to_string())?;
+        let path = repo_file(repo, self.path_parts(), true)?;
         println!("Writing ref to: {:?}", path);
-        serde_json::to_string(self)
-            .map_err(|e| e.to_string())
-            .and_then(|data| fs::write(path, data).or_else(|e| Err(e.to_string())))
+
+        let data = if repo.conf.git_compatible() {
+            match &self.target {
+                Some(RefType::Direct(sha)) => format!("{}\n", sha),
+                Some(RefType::Indirect(name)) => format!("ref: {}\n", name),
+                _ => unreachable!()
+            }
+        } else {
+            serde_json::to_string(self).map_err(|e| e.to_string())?
+        };
+
+        fs::write(path, data).map_err(|e| e.to_string())
+    }
+
+    pub fn exists(&self, repo: &Repository) -> bool {
+        Path::new(&repo_path(repo, self.path_parts())).is_file()
+    }
+
+    /// Remove the file of the ref, along with any directories of a nested name such as
+    /// refs/heads/feature/x which are left empty
+    pub fn delete(&self, repo: &Repository) -> Result<(), String> {
+        let path = repo_path(repo, self.path_parts());
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+
+        let refs_dir = repo_path(repo, vec!["refs".to_string()]);
+        let mut parent = Path::new(&path).parent();
+        while let Some(dir) = parent {
+            // leave the standard refs/heads and refs/tags dirs in place
+            if dir.parent() == Some(Path::new(&refs_dir)) || fs::remove_dir(dir).is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+
+        Ok(())
+    }
+
+    // Split the name so that nested names create nested directories
+    fn path_parts(&self) -> Vec<String> {
+        self.name.split('/').map(|part| part.to_string()).collect()
     }
 
     /// Given a reference, start with its name and resolve away any Indirect references to
@@ -123,14 +162,21 @@ impl Ref {
             return RefType::Broken;
         }
 
-        // TODO: a nicer way to chain these together? the two functions return different error types
-        //       could I map_err and turn them both to Strings? something like .map_err(|e| e.into())?
-        let read_ref: Ref = fs::read(path)
-            .and_then(|data| Ok(String::from_utf8(data).unwrap()))
-            .and_then(|data_str| Ok(serde_json::from_str(&data_str).unwrap()))
-            .unwrap();
+        let data = fs::read_to_string(path).unwrap();
+        Ref::parse_target(&data)
+    }
+
+    /// The refs we write ourselves are json, but HEAD and every ref of a git compatible repo are in the
+    /// plain text format of git so are either 'ref: <name of another ref>' or a bare sha
+    fn parse_target(data: &str) -> RefType {
+        if let Some(name) = data.strip_prefix("ref: ") {
+            return RefType::Indirect(name.trim().to_string());
+        }
 
-        return read_ref.target.unwrap()
+        match serde_json::from_str::<Ref>(data) {
+            Ok(read_ref) => read_ref.target.unwrap_or(RefType::Broken),
+            Err(_) => RefType::Direct(data.trim().to_string())
+        }
     }
 }
 
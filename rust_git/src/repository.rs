@@ -1,9 +1,18 @@
 use std::{env, fs};
 use std::fs::{create_dir_all, metadata};
 use std::path::{Path, PathBuf};
-use crate::config::{Config, ConfigContents};
-use crate::file_utils::{repo_dir, repo_file};
-use crate::index::Index;
+use bytes::Bytes;
+use walkdir::WalkDir;
+use crate::branch_utils::{branch_get_active, head_advance};
+use crate::cherry_pick::{pick_author, pick_state_clear};
+use crate::config::{Config, ConfigContents, ConfigFormat};
+use crate::file_utils::{normalize_path, repo_dir, repo_file};
+use crate::git_object::{GitBlob, GitCommit, GitObject, GitWriteable};
+use crate::ignore::Ignore;
+use crate::index::{Index, IndexEntry};
+use crate::merge::{merge_head_read, merge_state_clear};
+use crate::object_utils::{object_read, object_write, tree_from_index, tree_to_dict};
+use crate::refs::{Ref, RefType};
 
 #[derive(Debug)]
 pub struct Repository {
@@ -48,7 +57,19 @@ impl Repository {
     }
 
     pub fn create(path: String) -> Result<Self, String> {
-        let repo = Repository::new(path, true)?;
+        Repository::create_with_format(path, ConfigFormat::Json)
+    }
+
+    /// Create a repo whose config is written in the given format, an ini config makes the repo
+    /// git compatible so that git can work with it too
+    pub fn create_with_format(path: String, format: ConfigFormat) -> Result<Self, String> {
+        let mut repo = Repository::new(path, true)?;
+        repo.conf.format = format;
+
+        // like git init, which turns file modes on wherever the filesystem keeps executable bits
+        if format == ConfigFormat::Ini {
+            repo.conf.contents.core.filemode = cfg!(unix);
+        }
 
         let worktree = Path::new(&repo.worktree);
         let gitdir = Path::new(&repo.gitdir);
@@ -83,7 +104,7 @@ impl Repository {
         }
 
         let mut path_obj = PathBuf::from(&path_to_search);
-        println!("Searching for repo in: {:?}", path_obj);
+        eprintln!("Searching for repo in: {:?}", path_obj);
 
         path_obj.push(".git");
 
@@ -139,29 +160,270 @@ impl Repository {
 
     /// Given a list of paths, remove their entries from the index if
     /// present, optionally delete the files if specified
-    pub fn rm(&self, paths: Vec<String>, delete: bool, skip_missing: bool) {
-        let index = Index::read(&self);
+    ///
+    /// A directory removes every entry staged beneath it. If skip_missing is false a path
+    /// with no staged entries is an error. Unless forced we refuse to delete a file whose staged sha
+    /// differs from HEAD or from its worktree contents, as those changes would otherwise be lost. Keeping
+    /// the file only refuses when the staged sha matches neither, as then it is the only copy
+    pub fn rm(&self, paths: Vec<String>, delete: bool, skip_missing: bool, force: bool) -> Result<(), String> {
+        let mut index = Index::read(self)?;
+
+        let mut to_remove = Vec::new();
+        for path in paths {
+            let name = self.worktree_relative_path(&path)?;
+            let dir_prefix = format!("{}/", name);
+
+            let matched: Vec<String> = index.entries.iter()
+                .filter(|entry| name.is_empty() || entry.name == name || entry.name.starts_with(&dir_prefix))
+                .map(|entry| entry.name.clone())
+                .collect();
+
+            if matched.is_empty() && !skip_missing {
+                return Err(format!("pathspec '{}' did not match any files", name));
+            }
+
+            to_remove.extend(matched);
+        }
+
+        // Check everything up front so that we either remove all the paths or none of them
+        if !force {
+            let head = tree_to_dict(self, "HEAD", None)?;
+
+            for name in &to_remove {
+                let staged_sha = &index.get_entry(name).unwrap().sha;
+                let staged_changes = head.get(name) != Some(staged_sha);
+                // a file missing from the worktree has nothing left to lose
+                let local_changes = self.hash_worktree_file(name)?.is_some_and(|worktree_sha| &worktree_sha != staged_sha);
 
-        todo!("Implement rm functionality")
+                if delete && staged_changes {
+                    return Err(format!("{} has changes staged in the index, use force to remove it anyway", name));
+                }
+
+                if delete && local_changes {
+                    return Err(format!("{} has local modifications, use force to remove it anyway", name));
+                }
+
+                if !delete && staged_changes && local_changes {
+                    return Err(format!("{} has staged content different from both the file and HEAD, use force to remove it anyway", name));
+                }
+            }
+        }
+
+        for name in to_remove {
+            println!("Removing {}", name);
+            index.remove_entry(&name);
+
+            if delete {
+                self.delete_worktree_file(&name)?;
+            }
+        }
+
+        index.write(self)
     }
 
-    pub fn add(&self, paths: Vec<String>, delete: bool, skip_missing: bool) {
-        self.rm(paths.clone(), delete.clone(), skip_missing.clone());
+    /// Given a list of paths (absolute or relative to the current dir) hash each file into the
+    /// object store and stage it in the index, replacing any existing entry for that file.
+    ///
+    /// Directories are walked recursively and any file matched by the .gitignore is skipped.
+    /// Tracked files under a path which are gone from the worktree have their removal staged
+    pub fn add(&self, paths: Vec<String>) -> Result<(), String> {
+        let mut index = Index::read(self)?;
+        let ignore = Ignore::read(self);
+
+        for path in paths {
+            let name = self.worktree_relative_path(&path)?;
+            let dir_prefix = format!("{}/", name);
+
+            let deleted: Vec<String> = index.entries.iter()
+                .filter(|entry| name.is_empty() || entry.name == name || entry.name.starts_with(&dir_prefix))
+                .filter(|entry| fs::symlink_metadata(Path::new(&self.worktree).join(&entry.name)).is_err())
+                .map(|entry| entry.name.clone())
+                .collect();
+
+            for file_name in &deleted {
+                println!("Removing {}", file_name);
+                index.remove_entry(file_name);
+            }
 
-        // TODO: Deal with the path cleaning
+            // nothing is left to stage for a path which only named deleted files
+            if !deleted.is_empty() && fs::symlink_metadata(Path::new(&self.worktree).join(&name)).is_err() {
+                continue;
+            }
 
-        let index = Index::read(&self);
+            for file_name in self.expand_path(&name)? {
+                if let Some(true) = ignore.check_ignore(file_name.clone()) {
+                    println!("Skipping ignored file: {}", file_name);
+                    continue;
+                }
 
-        // TODO: use fs::metadata to get mtime etc. https://doc.rust-lang.org/std/fs/struct.Metadata.html
-        // let entry = IndexEntry::new()
+                let data = worktree_file_data(&Path::new(&self.worktree).join(&file_name))?;
+                let sha = object_write(GitObject::Blob(GitBlob::deserialize(Bytes::from(data))), Some(self))?;
 
+                println!("Staging {} as {}", file_name, sha);
+                index.remove_entry(&file_name);
+                index.add_entry(IndexEntry::from_worktree(self, sha, file_name));
+            }
+        }
+
+        index.sort();
+        index.write(self)
+    }
+
+    /// Commit the staged contents of the index with the given message, returning the sha of the commit.
+    ///
+    /// The commit becomes the new tip of the active branch, or the new HEAD if HEAD is detached.
+    /// When HEAD points at a branch with no commits yet (a broken ref) this creates a root commit.
+    /// If a merge is in progress the merged commit becomes the second parent, making this the merge commit
+    pub fn commit(&self, message: String) -> Result<String, String> {
+        let index = Index::read(self)?;
+
+        let conflicts = index.conflicted_paths();
+        if !conflicts.is_empty() {
+            return Err(format!("Committing is not possible because you have unmerged files:\n\t{}", conflicts.join("\n\t")));
+        }
+
+        let tree = tree_from_index(self, &index)?;
+
+        let mut parents = match Ref::new("HEAD".to_string()).fully_resolve(self) {
+            RefType::Direct(sha) => vec![sha],
+            _ => vec![]
+        };
+
+        let merge_head = merge_head_read(self);
+        match (&merge_head, parents.first()) {
+            (Some(merge_head), _) => parents.push(merge_head.clone()),
+            (None, Some(parent)) => {
+                let parent_tree = match object_read(self, parent.clone())? {
+                    GitObject::Commit(commit) => commit.get_tree_string(),
+                    _ => None
+                };
+
+                if parent_tree.as_ref() == Some(&tree) {
+                    return Err("Nothing to commit, the index matches HEAD".to_string());
+                }
+            },
+            (None, None) => {}
+        }
+
+        // a cherry-picked change keeps the author of the original commit
+        let author = pick_author(self).unwrap_or_else(|| self.conf.signature());
+        let commit = GitCommit::create_with_committer(tree, parents, author, self.conf.signature(), message);
+        let sha = object_write(GitObject::Commit(commit), Some(self))?;
+
+        head_advance(self, &sha)?;
+        merge_state_clear(self)?;
+        pick_state_clear(self)?;
+
+        match branch_get_active(self) {
+            Some(branch) => println!("[{}] committed {}", branch, sha),
+            None => println!("[detached HEAD] committed {}", sha)
+        }
+
+        Ok(sha)
+    }
+
+    /// Given a path on the command line, return it relative to the root of the worktree
+    /// which is how paths are stored in the index and in trees
+    pub fn worktree_relative_path(&self, path: &str) -> Result<String, String> {
+        let worktree = normalize_path(Path::new(&self.worktree));
+        let full_path = normalize_path(Path::new(path));
+
+        full_path.strip_prefix(&worktree)
+            .map(|relative| relative.to_str().unwrap().to_string())
+            .map_err(|_| format!("{} is outside repository at {}", path, self.worktree))
+    }
+
+    /// Expand a worktree relative path into the list of files it covers, walking
+    /// directories recursively but never descending into the gitdir
+    fn expand_path(&self, name: &str) -> Result<Vec<String>, String> {
+        let worktree = Path::new(&self.worktree);
+        let full_path = worktree.join(name);
+
+        // a symlink is staged as itself, even if it points at a directory
+        if full_path.is_symlink() || full_path.is_file() {
+            return Ok(vec![name.to_string()]);
+        }
+
+        if !full_path.is_dir() {
+            return Err(format!("pathspec '{}' did not match any files", name));
+        }
+
+        let mut files = Vec::new();
+        let walker = WalkDir::new(&full_path).sort_by_file_name().into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git");
+
+        for entry in walker.filter_map(|entry| entry.ok()) {
+            if entry.file_type().is_dir() {
+                continue;
+            }
+
+            let relative = entry.path().strip_prefix(worktree).map_err(|e| e.to_string())?;
+            files.push(relative.to_str().unwrap().to_string());
+        }
+
+        Ok(files)
+    }
+
+    /// Hash a file in the worktree without writing it to the object store,
+    /// returns None if the file does not exist
+    pub fn hash_worktree_file(&self, name: &str) -> Result<Option<String>, String> {
+        let path = Path::new(&self.worktree).join(name);
+        if !path.is_symlink() && !path.is_file() {
+            return Ok(None);
+        }
+
+        let data = worktree_file_data(&path)?;
+        object_write(GitObject::Blob(GitBlob::deserialize(Bytes::from(data))), None).map(Some)
+    }
+
+    /// Delete a file from the worktree along with any parent directories left empty by its removal
+    pub fn delete_worktree_file(&self, name: &str) -> Result<(), String> {
+        let worktree = Path::new(&self.worktree);
+        let path = worktree.join(name);
+
+        if path.is_symlink() || path.exists() {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if dir == worktree || !dir.is_dir() || dir.read_dir().map_err(|e| e.to_string())?.next().is_some() {
+                break;
+            }
+
+            fs::remove_dir(dir).map_err(|e| e.to_string())?;
+            parent = dir.parent();
+        }
+
+        Ok(())
     }
 }
 
+/// The contents of a worktree file as stored in its blob, which for a symlink is the path it points at
+fn worktree_file_data(path: &Path) -> Result<Vec<u8>, String> {
+    if path.is_symlink() {
+        let target = fs::read_link(path).map_err(|e| e.to_string())?;
+        return Ok(target.to_string_lossy().into_owned().into_bytes());
+    }
+
+    fs::read(path).map_err(|e| e.to_string())
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::fs;
+    use std::path::Path;
+    use bytes::Bytes;
     use tempdir::TempDir;
+    use walkdir::WalkDir;
+    use crate::branch_utils::branch_list;
+    use crate::git_object::{GitObject, GitTag};
+    use crate::index::Index;
+    use crate::key_value_list_message::{KeyValuePairEntry, KeyValuePairKey};
+    use crate::object_utils::{object_find, object_read, tree_to_dict};
+    use crate::refs::{Ref, RefType};
     use crate::repository::Repository;
+    use crate::status::Status;
 
     #[test]
     fn repo_create_new_and_find() {
@@ -192,4 +454,280 @@ mod tests {
 
         tmp_dir.close().unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn repo_add_files() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join(".gitignore"), "*.log").unwrap();
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("inner/nested")).unwrap();
+        fs::write(tmp_dir.path().join("inner/second.txt"), "second file").unwrap();
+        fs::write(tmp_dir.path().join("inner/nested/third.txt"), "third file").unwrap();
+        fs::write(tmp_dir.path().join("inner/debug.log"), "ignored file").unwrap();
+
+        // stage the gitignore first so that it applies to the next add
+        let gitignore_path = tmp_dir.path().join(".gitignore").to_str().unwrap().to_string();
+        assert!(repo.add(vec![gitignore_path]).is_ok());
+
+        let add_result = repo.add(vec![tmp_dir_string.clone()]);
+        assert!(add_result.is_ok());
+
+        let index = Index::read(&repo).unwrap();
+        let names: Vec<String> = index.entries.iter().map(|entry| entry.name.clone()).collect();
+        assert_eq!(names, vec![".gitignore", "first.txt", "inner/nested/third.txt", "inner/second.txt"]);
+
+        // re-adding a changed file replaces the stale entry rather than duplicating it
+        let first_sha = index.get_entry("first.txt").unwrap().sha.clone();
+        fs::write(tmp_dir.path().join("first.txt"), "first file changed").unwrap();
+        let first_path = tmp_dir.path().join("first.txt").to_str().unwrap().to_string();
+        assert!(repo.add(vec![first_path]).is_ok());
+
+        let index = Index::read(&repo).unwrap();
+        assert_eq!(index.entries.len(), 4);
+        assert_ne!(index.get_entry("first.txt").unwrap().sha, first_sha);
+
+        // adding a deleted file, or a directory it was in, stages its removal
+        fs::remove_file(tmp_dir.path().join("first.txt")).unwrap();
+        fs::remove_dir_all(tmp_dir.path().join("inner/nested")).unwrap();
+        assert!(repo.add(vec![tmp_dir.path().join("first.txt").to_str().unwrap().to_string()]).is_ok());
+        assert!(repo.add(vec![tmp_dir.path().join("inner").to_str().unwrap().to_string()]).is_ok());
+        let index = Index::read(&repo).unwrap();
+        let names: Vec<String> = index.entries.iter().map(|entry| entry.name.clone()).collect();
+        assert_eq!(names, vec![".gitignore", "inner/second.txt"]);
+        assert!(repo.add(vec![tmp_dir.path().join("first.txt").to_str().unwrap().to_string()]).is_err());
+
+        tmp_dir.close().unwrap();
+    }
+
+    fn create_repo_with_files(tmp_dir: &TempDir) -> Repository {
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("inner")).unwrap();
+        fs::write(tmp_dir.path().join("inner/second.txt"), "second file").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+
+        repo
+    }
+
+    /// Write a file into the worktree, stage it and commit, returning the new commit's sha
+    pub(crate) fn commit_file(repo: &Repository, tmp_dir: &TempDir, name: &str, contents: &str, message: &str) -> String {
+        fs::write(tmp_dir.path().join(name), contents).unwrap();
+        repo.add(vec![tmp_dir.path().join(name).to_str().unwrap().to_string()]).unwrap();
+        repo.commit(message.to_string()).unwrap()
+    }
+
+    #[test]
+    fn repo_rm_cached_keeps_file() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = create_repo_with_files(&tmp_dir);
+
+        repo.commit("First commit".to_string()).unwrap();
+
+        let first_path = tmp_dir.path().join("first.txt").to_str().unwrap().to_string();
+        let rm_result = repo.rm(vec![first_path], false, false, false);
+        assert!(rm_result.is_ok());
+
+        let index = Index::read(&repo).unwrap();
+        assert!(index.get_entry("first.txt").is_none());
+        assert!(index.get_entry("inner/second.txt").is_some());
+        assert!(tmp_dir.path().join("first.txt").exists());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn repo_rm_deletes_directory() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = create_repo_with_files(&tmp_dir);
+
+        repo.commit("First commit".to_string()).unwrap();
+
+        let inner_path = tmp_dir.path().join("inner").to_str().unwrap().to_string();
+        let rm_result = repo.rm(vec![inner_path], true, false, false);
+        assert!(rm_result.is_ok());
+
+        let index = Index::read(&repo).unwrap();
+        assert_eq!(index.entries.len(), 1);
+        assert!(!tmp_dir.path().join("inner/second.txt").exists());
+        assert!(!tmp_dir.path().join("inner").exists());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn repo_rm_refuses_modified_and_missing() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = create_repo_with_files(&tmp_dir);
+        repo.commit("First commit".to_string()).unwrap();
+
+        fs::write(tmp_dir.path().join("first.txt"), "unstaged change").unwrap();
+        let first_path = tmp_dir.path().join("first.txt").to_str().unwrap().to_string();
+        assert!(repo.rm(vec![first_path.clone()], true, false, false).is_err());
+        assert!(Index::read(&repo).unwrap().get_entry("first.txt").is_some());
+
+        // a staged change would be lost by deleting the file, but not by keeping it
+        repo.add(vec![first_path.clone()]).unwrap();
+        assert!(repo.rm(vec![first_path.clone()], true, false, false).is_err());
+        fs::write(tmp_dir.path().join("first.txt"), "another change").unwrap();
+        assert!(repo.rm(vec![first_path.clone()], false, false, false).is_err());
+        assert!(Index::read(&repo).unwrap().get_entry("first.txt").is_some());
+
+        // a path which was never staged is only an error if we dont skip missing paths
+        let missing_path = tmp_dir.path().join("missing.txt").to_str().unwrap().to_string();
+        assert!(repo.rm(vec![missing_path.clone()], true, false, false).is_err());
+        assert!(repo.rm(vec![missing_path], true, true, false).is_ok());
+
+        assert!(repo.rm(vec![first_path], true, false, true).is_ok());
+        assert!(!tmp_dir.path().join("first.txt").exists());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn repo_commit_root_and_child() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = create_repo_with_files(&tmp_dir);
+
+        // HEAD points at a branch with no commits yet, so this is a root commit
+        assert_eq!(Ref::new("HEAD".to_string()).fully_resolve(&repo), RefType::Broken);
+        let first_sha = repo.commit("First commit".to_string()).unwrap();
+        assert_eq!(Ref::new("HEAD".to_string()).fully_resolve(&repo), RefType::Direct(first_sha.clone()));
+
+        let first_commit = match object_read(&repo, first_sha.clone()).unwrap() {
+            GitObject::Commit(commit) => commit,
+            _ => panic!("Expected a commit")
+        };
+        assert!(first_commit.data.get(KeyValuePairKey::Key("parent".to_string())).is_none());
+
+        // one subtree per directory
+        let tree = first_commit.get_and_read_tree(&repo).unwrap();
+        let paths: Vec<String> = tree.items.iter().map(|leaf| leaf.path.clone()).collect();
+        assert_eq!(paths, vec!["first.txt", "inner"]);
+        assert!(tree.items[1].is_tree());
+
+        let subtree = match object_read(&repo, tree.items[1].sha.clone()).unwrap() {
+            GitObject::Tree(subtree) => subtree,
+            _ => panic!("Expected a tree")
+        };
+        assert_eq!(subtree.items[0].path, "second.txt");
+
+        // with nothing newly staged there is nothing to commit
+        assert!(repo.commit("Empty commit".to_string()).is_err());
+
+        fs::write(tmp_dir.path().join("first.txt"), "first file changed").unwrap();
+        repo.add(vec![tmp_dir.path().join("first.txt").to_str().unwrap().to_string()]).unwrap();
+        let second_sha = repo.commit("Second commit".to_string()).unwrap();
+
+        let second_commit = match object_read(&repo, second_sha.clone()).unwrap() {
+            GitObject::Commit(commit) => commit,
+            _ => panic!("Expected a commit")
+        };
+        let parent = second_commit.data.get(KeyValuePairKey::Key("parent".to_string()));
+        assert_eq!(parent, Some(&KeyValuePairEntry::Singleton(Bytes::from(first_sha))));
+        assert_eq!(Ref::new("refs/heads/master".to_string()).fully_resolve(&repo), RefType::Direct(second_sha));
+
+        tmp_dir.close().unwrap();
+    }
+
+    /// Copy a repo made by git from tests/fixtures into the temp dir. Its .git is checked in as dot_git,
+    /// as git wont track a directory of that name
+    fn open_fixture(tmp_dir: &TempDir, name: &str) -> Repository {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+
+        for file in WalkDir::new(&fixture).into_iter().filter_map(|file| file.ok()) {
+            let relative = file.path().strip_prefix(&fixture).unwrap().to_str().unwrap();
+            let relative = relative.replacen("dot_git", ".git", 1);
+            let target = tmp_dir.path().join(relative);
+
+            if file.file_type().is_dir() {
+                fs::create_dir_all(target).unwrap();
+            } else {
+                fs::copy(file.path(), target).unwrap();
+            }
+        }
+
+        Repository::find(tmp_dir.path().join("src").to_str().unwrap().to_string(), false).unwrap()
+    }
+
+    #[test]
+    fn repo_read_git_fixture() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = open_fixture(&tmp_dir, "git_repo");
+        assert!(repo.conf.git_compatible());
+        assert_eq!(repo.conf.identity(), "Fixture Author <author@example.com>");
+
+        let head = object_find(&repo, "HEAD", None, true).unwrap();
+        assert_eq!(head, "f200b5de14d79926fdc61b3e2519f42d4a29771d");
+        let commit = match object_read(&repo, head.clone()).unwrap() {
+            GitObject::Commit(commit) => commit,
+            _ => panic!("Expected a commit")
+        };
+        assert_eq!(commit.summary(), "Add the library and a script");
+        assert_eq!(commit.get_parents(), vec!["0c6047a42a84111b8abdb37457d1d7e620ae65f3".to_string()]);
+        assert_eq!(branch_list(&repo), vec!["feature".to_string(), "master".to_string()]);
+
+        let tree = tree_to_dict(&repo, &head, None).unwrap();
+        assert_eq!(tree.get("src/lib.rs").map(|sha| sha.as_str()), Some("432593368f9028b66c60c5f011dd54949c10ca81"));
+        assert_eq!(tree.len(), 3);
+
+        // an annotated tag, and a lightweight one which is only a ref
+        let tag = match object_read(&repo, object_find(&repo, "v1.0", None, false).unwrap()).unwrap() {
+            GitObject::Tag(tag) => tag,
+            _ => panic!("Expected a tag")
+        };
+        assert_eq!(tag.peel(&repo), Ok(head.clone()));
+        assert_eq!(tag.message(), Some("First release".to_string()));
+        assert_eq!(GitTag::list(&repo), vec!["light".to_string(), "v1.0".to_string()]);
+        assert_eq!(object_find(&repo, "light", None, true), Ok("0c6047a42a84111b8abdb37457d1d7e620ae65f3".to_string()));
+
+        // the binary index, with the executable bit of the script
+        let index = Index::read(&repo).unwrap();
+        let modes: Vec<(String, String)> = index.entries.iter().map(|entry| (entry.mode(), entry.name.clone())).collect();
+        assert_eq!(modes, vec![("100644".to_string(), "README.md".to_string()), ("100755".to_string(), "run.sh".to_string()),
+                               ("100644".to_string(), "src/lib.rs".to_string())]);
+        assert!(Status::read(&repo).unwrap().is_clean());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn repo_commit_into_git_fixture() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let repo = open_fixture(&tmp_dir, "git_repo");
+
+        fs::write(tmp_dir.path().join("README.md"), "# Fixture\n\nChanged by us\n").unwrap();
+        repo.add(vec![tmp_dir.path().join("README.md").to_str().unwrap().to_string()]).unwrap();
+        let sha = repo.commit("Change the readme".to_string()).unwrap();
+
+        // everything is written in the formats git reads
+        let gitdir = tmp_dir.path().join(".git");
+        assert_eq!(fs::read_to_string(gitdir.join("refs/heads/master")).unwrap(), format!("{}\n", sha));
+        assert!(fs::read(gitdir.join("index")).unwrap().starts_with(b"DIRC"));
+        let config = fs::read_to_string(gitdir.join("config")).unwrap();
+        assert!(config.starts_with("[core]"));
+
+        // the unchanged tree entries keep the shas git gave them, so the trees are encoded the same way
+        let commit = match object_read(&repo, sha.clone()).unwrap() {
+            GitObject::Commit(commit) => commit,
+            _ => panic!("Expected a commit")
+        };
+        assert_eq!(commit.get_parents(), vec!["f200b5de14d79926fdc61b3e2519f42d4a29771d".to_string()]);
+        let tree = tree_to_dict(&repo, &sha, None).unwrap();
+        assert_eq!(tree.get("src/lib.rs").map(|sha| sha.as_str()), Some("432593368f9028b66c60c5f011dd54949c10ca81"));
+        assert_eq!(tree.get("src").map(|sha| sha.as_str()), None);
+        assert_eq!(Index::read(&repo).unwrap().get_entry("run.sh").map(|entry| entry.mode()), Some("100755".to_string()));
+
+        // reading back an unchanged index gives the same bytes
+        let index_data = fs::read(gitdir.join("index")).unwrap();
+        Index::read(&repo).unwrap().write(&repo).unwrap();
+        assert_eq!(fs::read(gitdir.join("index")).unwrap(), index_data);
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::branch_utils::head_advance;
+use crate::checkout::{checkout_write_blob, restore_paths};
+use crate::cherry_pick::{pick_in_progress, pick_state_clear};
+use crate::index::{Index, IndexEntry};
+use crate::merge::{merge_head_read, merge_state_clear};
+use crate::object_utils::{object_find, tree_to_entries};
+use crate::rebase::{rebase_in_progress, rebase_state_clear};
+use crate::refs::{Ref, RefType};
+use crate::repository::Repository;
+
+/// Where HEAD was before the last reset, so 'reset ORIG_HEAD' undoes it
+const ORIG_HEAD: &str = "ORIG_HEAD";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResetMode {
+    // Only move the branch, leaving the index and worktree as they are
+    Soft,
+    // Move the branch and make the index match the commit, keeping the worktree
+    Mixed,
+    // Move the branch and make both the index and worktree match the commit
+    Hard
+}
+
+/// Move the branch HEAD points at (or HEAD itself when detached) to another commit, which can be
+/// an ancestor so this is how commits are undone. The mode decides how much else follows it.
+///
+/// The old HEAD is saved as ORIG_HEAD once the reset has succeeded. Any merge, cherry-pick, revert
+/// or rebase in progress is abandoned, except by a soft reset which would leave its conflicts behind
+/// and is refused instead
+pub fn reset(repo: &Repository, name: &str, mode: ResetMode) -> Result<String, String> {
+    let target = object_find(repo, name, Some("commit"), true)?;
+    let rebasing = rebase_in_progress(repo);
+    let in_progress = merge_head_read(repo).is_some() || pick_in_progress(repo).is_some() || rebasing;
+
+    if mode == ResetMode::Soft && in_progress {
+        return Err("Cannot do a soft reset in the middle of a merge, cherry-pick, revert or rebase".to_string());
+    }
+
+    let head = Ref::new("HEAD".to_string()).fully_resolve(repo);
+
+    let target_tree = tree_to_entries(repo, &target)?;
+    match mode {
+        ResetMode::Soft => {},
+        ResetMode::Mixed => reset_index(repo, &target_tree)?,
+        ResetMode::Hard => reset_worktree(repo, &target_tree)?
+    }
+
+    head_advance(repo, &target)?;
+
+    if let RefType::Direct(head) = head {
+        let mut orig_head = Ref::new(ORIG_HEAD.to_string());
+        orig_head.add_target(RefType::Direct(head));
+        orig_head.write(repo)?;
+    }
+
+    if in_progress {
+        merge_state_clear(repo)?;
+        pick_state_clear(repo)?;
+    }
+
+    if rebasing {
+        rebase_state_clear(repo)?;
+    }
+
+    Ok(target)
+}
+
+/// Reset the index entries of some paths to a commit, so that changes to them are no longer staged.
+/// HEAD and the worktree are left alone
+pub fn reset_paths(repo: &Repository, name: &str, paths: &[String]) -> Result<(), String> {
+    object_find(repo, name, Some("commit"), true)?;
+    restore_paths(repo, paths.to_vec(), true, false, Some(name))
+}
+
+/// Rebuild the index from a tree, keeping the cached metadata of entries which dont change
+fn reset_index(repo: &Repository, target_tree: &HashMap<String, (String, String)>) -> Result<(), String> {
+    let old_index = Index::read(repo)?;
+    let mut index = Index::new();
+
+    for (path, (sha, mode)) in target_tree {
+        match old_index.get_stage(path, 0) {
+            Some(entry) if &entry.sha == sha && &entry.mode() == mode => index.add_entry(entry.clone()),
+            _ => {
+                let mut entry = IndexEntry::from_blob(sha.clone(), path.clone());
+                entry.set_mode(mode);
+                index.add_entry(entry);
+            }
+        }
+    }
+
+    index.sort();
+    index.write(repo)
+}
+
+/// Make the index and the tracked files of the worktree match a tree, throwing away local changes.
+/// Untracked files are left alone
+fn reset_worktree(repo: &Repository, target_tree: &HashMap<String, (String, String)>) -> Result<(), String> {
+    // a conflicted path has several entries but only needs removing once
+    let tracked: BTreeSet<String> = Index::read(repo)?.entries.into_iter().map(|entry| entry.name).collect();
+
+    for path in tracked.iter().filter(|path| !target_tree.contains_key(*path)) {
+        println!("Removing {}", path);
+        repo.delete_worktree_file(path)?;
+    }
+
+    let mut index = Index::new();
+    for (path, (sha, mode)) in target_tree {
+        let unchanged = repo.hash_worktree_file(path)?.as_deref() == Some(sha.as_str())
+            && &IndexEntry::from_worktree(repo, sha.clone(), path.clone()).mode() == mode;
+
+        if !unchanged {
+            println!("Updating {}", path);
+            checkout_write_blob(repo, sha, path, Some(mode))?;
+        }
+        index.add_entry(IndexEntry::from_worktree(repo, sha.clone(), path.clone()));
+    }
+
+    index.sort();
+    index.write(repo)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::branch_utils::branch_switch;
+    use crate::checkout::checkout_in_place;
+    use crate::commit_graph::commit_read;
+    use crate::file_utils::repo_file;
+    use crate::index::Index;
+    use crate::merge::{merge, merge_head_read, MergeOutcome};
+    use crate::object_utils::object_find;
+    use crate::repository::Repository;
+    use crate::repository::tests::commit_file;
+    use crate::rebase::{rebase, rebase_continue, rebase_in_progress, RebaseOutcome};
+    use crate::reset::{reset, reset_paths, ResetMode};
+    use crate::status::{FileChange, Status};
+
+    #[test]
+    fn reset_soft_mixed_hard() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string).unwrap();
+
+        let first = commit_file(&repo, &tmp_dir, "file.txt", "first", "First");
+        let second = commit_file(&repo, &tmp_dir, "added.txt", "added", "Second");
+
+        // soft keeps the second commit's changes staged
+        reset(&repo, "HEAD~1", ResetMode::Soft).unwrap();
+        assert_eq!(object_find(&repo, "master", None, true), Ok(first.clone()));
+        assert_eq!(object_find(&repo, "ORIG_HEAD", None, true), Ok(second.clone()));
+        let status = Status::read(&repo).unwrap();
+        assert_eq!(status.staged, vec![(FileChange::Added, "added.txt".to_string())]);
+
+        // which can be undone through ORIG_HEAD
+        reset(&repo, "ORIG_HEAD", ResetMode::Soft).unwrap();
+        assert_eq!(object_find(&repo, "master", None, true), Ok(second.clone()));
+        assert!(Status::read(&repo).unwrap().is_clean());
+
+        // mixed leaves them in the worktree but not the index
+        reset(&repo, &first, ResetMode::Mixed).unwrap();
+        let status = Status::read(&repo).unwrap();
+        assert!(status.staged.is_empty());
+        assert_eq!(status.untracked, vec!["added.txt".to_string()]);
+        assert!(Index::read(&repo).unwrap().get_entry("added.txt").is_none());
+
+        // hard throws away changes to tracked files and removes files the target doesnt have
+        reset(&repo, &second, ResetMode::Hard).unwrap();
+        fs::write(tmp_dir.path().join("file.txt"), "local change").unwrap();
+        fs::write(tmp_dir.path().join("untracked.txt"), "untracked").unwrap();
+        reset(&repo, &first, ResetMode::Hard).unwrap();
+
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("file.txt")).unwrap(), "first");
+        assert!(!tmp_dir.path().join("added.txt").exists());
+        assert!(tmp_dir.path().join("untracked.txt").exists());
+        let status = Status::read(&repo).unwrap();
+        assert!(status.staged.is_empty() && status.unstaged.is_empty());
+
+        // an unreadable target tree fails before anything is touched, rather than emptying the worktree
+        let tree = commit_read(&repo, &second).unwrap().get_tree_string().unwrap();
+        fs::remove_file(repo_file(&repo, vec!["objects".to_string(), tree[..2].to_string(), tree[2..].to_string()], false).unwrap()).unwrap();
+        assert!(reset(&repo, &second, ResetMode::Hard).is_err());
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("file.txt")).unwrap(), "first");
+        assert_eq!(object_find(&repo, "master", None, true), Ok(first));
+        assert_eq!(object_find(&repo, "ORIG_HEAD", None, true), Ok(second));
+        assert_eq!(Index::read(&repo).unwrap().entries.len(), 1);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn reset_paths_and_merge_state() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        commit_file(&repo, &tmp_dir, "shared.txt", "one\n", "Root");
+        fs::write(tmp_dir.path().join("shared.txt"), "staged\n").unwrap();
+        fs::write(tmp_dir.path().join("other.txt"), "other\n").unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+
+        // only the given path is unstaged, its worktree change stays
+        reset_paths(&repo, "HEAD", &[tmp_dir.path().join("shared.txt").to_str().unwrap().to_string()]).unwrap();
+        let status = Status::read(&repo).unwrap();
+        assert_eq!(status.staged, vec![(FileChange::Added, "other.txt".to_string())]);
+        assert_eq!(status.unstaged, vec![(FileChange::Modified, "shared.txt".to_string())]);
+        reset(&repo, "HEAD", ResetMode::Hard).unwrap();
+
+        branch_switch(&repo, "feature", true).unwrap();
+        commit_file(&repo, &tmp_dir, "shared.txt", "theirs\n", "Side");
+        checkout_in_place(&repo, "master").unwrap();
+        let main = commit_file(&repo, &tmp_dir, "shared.txt", "ours\n", "Main");
+
+        assert_eq!(merge(&repo, "feature"), Ok(MergeOutcome::Conflicts(vec!["shared.txt".to_string()])));
+        assert!(reset(&repo, "HEAD", ResetMode::Soft).is_err());
+
+        // a hard reset abandons the conflicted merge
+        reset(&repo, "HEAD", ResetMode::Hard).unwrap();
+        assert_eq!(merge_head_read(&repo), None);
+        assert!(Index::read(&repo).unwrap().conflicted_paths().is_empty());
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("shared.txt")).unwrap(), "ours\n");
+        assert_eq!(object_find(&repo, "HEAD", None, true), Ok(main.clone()));
+
+        // and a stopped rebase, so it cannot be continued on top of the reset HEAD
+        assert_eq!(rebase(&repo, "feature", None), Ok(RebaseOutcome::Conflicts(vec!["shared.txt".to_string()])));
+        assert!(reset(&repo, "HEAD", ResetMode::Soft).is_err());
+        reset(&repo, "HEAD", ResetMode::Hard).unwrap();
+        assert!(!rebase_in_progress(&repo));
+        assert!(rebase_continue(&repo).is_err());
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+use crate::branch_utils::branch_get_previous;
+use crate::git_object::GitObject;
+use crate::index::Index;
+use crate::object_utils::{object_peel, object_read, object_resolve_name};
+use crate::repository::Repository;
+
+/// A revision expression as described in 'git help revisions', made up of a base name
+/// followed by any number of ancestry and peel operators, and optionally a path inside the tree
+///
+/// e.g. HEAD~3, main^2, v1.0^{tree}, @{-1} or HEAD:src/lib.rs
+#[derive(Debug, PartialEq)]
+pub struct Revision {
+    pub base: RevisionBase,
+    pub steps: Vec<RevisionStep>,
+    // Set for <rev>:<path>, addressing an object inside the tree of the revision
+    pub path: Option<String>
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RevisionBase {
+    // A name to resolve via refs or short hashes, '@' on its own is an alias for HEAD
+    Name(String),
+    // @{-n}, the nth branch checked out before the current one
+    PreviousCheckout(usize),
+    // :<path>, the blob staged in the index (with no revision before the colon)
+    Index
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RevisionStep {
+    // ~n, follow the first parent n times
+    Ancestor(usize),
+    // ^n, the nth parent of a commit where ^0 is the commit itself
+    Parent(usize),
+    // ^{type} peels until we reach an object of that type, ^{} peels away any tags
+    Peel(Option<String>)
+}
+
+/// A range of commits as accepted by log style commands
+#[derive(Debug, PartialEq)]
+pub enum RevisionRange {
+    // Everything reachable from the revision
+    Single(String),
+    // a..b, everything reachable from b but not from a
+    TwoDot { exclude: String, include: String },
+    // a...b, everything reachable from either a or b but not from both
+    ThreeDot { left: String, right: String }
+}
+
+impl Revision {
+    /// Parse a revision expression without touching the repo
+    ///
+    /// ```
+    /// use rust_git::revision::{Revision, RevisionBase, RevisionStep};
+    /// let revision = Revision::parse("main~2^2^{tree}").unwrap();
+    ///
+    /// assert_eq!(revision.base, RevisionBase::Name("main".to_string()));
+    /// assert_eq!(revision.steps, vec![
+    ///     RevisionStep::Ancestor(2),
+    ///     RevisionStep::Parent(2),
+    ///     RevisionStep::Peel(Some("tree".to_string()))
+    /// ]);
+    /// assert_eq!(revision.path, None);
+    /// ```
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = expr.trim();
+
+        let (rev, path) = match expr.split_once(':') {
+            Some((rev, path)) => (rev, Some(path.to_string())),
+            None => (expr, None)
+        };
+
+        if rev.is_empty() {
+            return match path {
+                Some(path) => Ok(Revision { base: RevisionBase::Index, steps: vec![], path: Some(path) }),
+                None => Err("Empty revision".to_string())
+            };
+        }
+
+        let base_end = rev.find(['~', '^']).unwrap_or(rev.len());
+        let (base, operators) = rev.split_at(base_end);
+
+        Ok(Revision {
+            base: Revision::parse_base(base)?,
+            steps: Revision::parse_steps(operators)?,
+            path
+        })
+    }
+
+    fn parse_base(base: &str) -> Result<RevisionBase, String> {
+        if base == "@" {
+            return Ok(RevisionBase::Name("HEAD".to_string()));
+        }
+
+        if let Some(previous) = base.strip_prefix("@{-").and_then(|rest| rest.strip_suffix('}')) {
+            return match previous.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(RevisionBase::PreviousCheckout(n)),
+                _ => Err(format!("Invalid previous checkout {}", base))
+            };
+        }
+
+        if base.is_empty() || base.contains("@{") {
+            return Err(format!("Unsupported revision {}", base));
+        }
+
+        Ok(RevisionBase::Name(base.to_string()))
+    }
+
+    fn parse_steps(operators: &str) -> Result<Vec<RevisionStep>, String> {
+        let mut steps = Vec::new();
+        let mut chars = operators.chars().peekable();
+
+        while let Some(operator) = chars.next() {
+            if operator == '^' && chars.peek() == Some(&'{') {
+                chars.next();
+                let peel_type: String = chars.by_ref().take_while(|&c| c != '}').collect();
+
+                steps.push(match peel_type.as_str() {
+                    "" => RevisionStep::Peel(None),
+                    "commit" | "tree" | "blob" | "tag" => RevisionStep::Peel(Some(peel_type)),
+                    other => return Err(format!("Unsupported peel type {}", other))
+                });
+                continue;
+            }
+
+            let mut digits = String::new();
+            while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(*c);
+                chars.next();
+            }
+
+            // A bare ~ or ^ means one step
+            let n = if digits.is_empty() {
+                1
+            } else {
+                digits.parse::<usize>().map_err(|e| e.to_string())?
+            };
+
+            steps.push(match operator {
+                '~' => RevisionStep::Ancestor(n),
+                '^' => RevisionStep::Parent(n),
+                other => return Err(format!("Unexpected character {} in revision", other))
+            });
+        }
+
+        Ok(steps)
+    }
+}
+
+impl RevisionRange {
+    /// Split a range expression into its sides, where an empty side defaults to HEAD
+    ///
+    /// ```
+    /// use rust_git::revision::RevisionRange;
+    /// assert_eq!(RevisionRange::parse("main..feature"), RevisionRange::TwoDot { exclude: "main".to_string(), include: "feature".to_string() });
+    /// assert_eq!(RevisionRange::parse("v1.0..."), RevisionRange::ThreeDot { left: "v1.0".to_string(), right: "HEAD".to_string() });
+    /// assert_eq!(RevisionRange::parse("HEAD~3"), RevisionRange::Single("HEAD~3".to_string()));
+    /// ```
+    pub fn parse(expr: &str) -> Self {
+        let or_head = |side: &str| if side.is_empty() { "HEAD".to_string() } else { side.to_string() };
+
+        if let Some((left, right)) = expr.split_once("...") {
+            return RevisionRange::ThreeDot { left: or_head(left), right: or_head(right) };
+        }
+
+        if let Some((exclude, include)) = expr.split_once("..") {
+            return RevisionRange::TwoDot { exclude: or_head(exclude), include: or_head(include) };
+        }
+
+        RevisionRange::Single(expr.to_string())
+    }
+}
+
+/// Parse a revision expression and resolve it to the sha of a single object
+pub fn revision_resolve(repo: &Repository, expr: &str) -> Result<String, String> {
+    let revision = Revision::parse(expr)?;
+
+    let mut sha = match &revision.base {
+        RevisionBase::Name(name) => object_resolve_name(repo, name)?,
+        RevisionBase::PreviousCheckout(n) => object_resolve_name(repo, &branch_get_previous(repo, *n)?)?,
+        RevisionBase::Index => {
+            let path = revision.path.unwrap_or_default();
+            return Index::read(repo)?.get_entry(&path)
+                .map(|entry| entry.sha.clone())
+                .ok_or(format!("Path {} is not in the index", path));
+        }
+    };
+
+    for step in &revision.steps {
+        sha = match step {
+            RevisionStep::Ancestor(n) => {
+                let mut ancestor = sha;
+                for _ in 0..*n {
+                    ancestor = revision_parent(repo, ancestor, 1)?;
+                }
+                ancestor
+            },
+            RevisionStep::Parent(0) => object_peel(repo, sha, "commit")?,
+            RevisionStep::Parent(n) => revision_parent(repo, sha, *n)?,
+            RevisionStep::Peel(Some(format)) => object_peel(repo, sha, format)?,
+            RevisionStep::Peel(None) => match object_read(repo, sha.clone())? {
+                GitObject::Tag(tag) => tag.peel(repo)?,
+                _ => sha
+            }
+        };
+    }
+
+    match revision.path {
+        Some(path) => revision_tree_path(repo, sha, &path),
+        None => Ok(sha)
+    }
+}
+
+/// The nth parent (counting from 1) of the commit the object peels to
+fn revision_parent(repo: &Repository, sha: String, n: usize) -> Result<String, String> {
+    let commit_sha = object_peel(repo, sha, "commit")?;
+
+    match object_read(repo, commit_sha.clone())? {
+        GitObject::Commit(commit) => commit.get_parents().get(n - 1).cloned()
+            .ok_or(format!("Commit {} has no parent number {}", commit_sha, n)),
+        _ => Err(format!("{} is not a commit", commit_sha))
+    }
+}
+
+/// Walk down the tree of the revision one path component at a time to find the sha at that path
+fn revision_tree_path(repo: &Repository, sha: String, path: &str) -> Result<String, String> {
+    let mut sha = object_peel(repo, sha, "tree")?;
+
+    for component in path.split('/').filter(|component| !component.is_empty()) {
+        let tree = match object_read(repo, sha.clone())? {
+            GitObject::Tree(tree) => tree,
+            _ => return Err(format!("Path {} goes through a file", path))
+        };
+
+        sha = tree.items.iter()
+            .find(|leaf| leaf.path == component)
+            .map(|leaf| leaf.sha.clone())
+            .ok_or(format!("Path {} does not exist in the tree", path))?;
+    }
+
+    Ok(sha)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use crate::git_object::{GitBlob, GitCommit, GitObject};
+    use crate::object_utils::{object_find, object_write};
+    use crate::refs::{Ref, RefType};
+    use crate::repository::Repository;
+    use crate::revision::{revision_resolve, Revision, RevisionBase, RevisionStep};
+
+    #[test]
+    fn revision_parse_operators() {
+        let revision = Revision::parse("HEAD^^{}~").unwrap();
+        assert_eq!(revision.steps, vec![RevisionStep::Parent(1), RevisionStep::Peel(None), RevisionStep::Ancestor(1)]);
+
+        let revision = Revision::parse("@{-2}:src/lib.rs").unwrap();
+        assert_eq!(revision.base, RevisionBase::PreviousCheckout(2));
+        assert_eq!(revision.path, Some("src/lib.rs".to_string()));
+
+        assert_eq!(Revision::parse(":README.md").unwrap().base, RevisionBase::Index);
+        assert_eq!(Revision::parse("@").unwrap().base, RevisionBase::Name("HEAD".to_string()));
+        assert!(Revision::parse("HEAD^{nonsense}").is_err());
+        assert!(Revision::parse("@{-0}").is_err());
+    }
+
+    #[test]
+    fn revision_resolve_history() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        let mut commits = Vec::new();
+        for i in 0..3 {
+            fs::create_dir_all(tmp_dir.path().join("inner")).unwrap();
+            fs::write(tmp_dir.path().join("inner/file.txt"), format!("version {}", i)).unwrap();
+            repo.add(vec![tmp_dir_string.clone()]).unwrap();
+            commits.push(repo.commit(format!("Commit {}", i)).unwrap());
+        }
+
+        assert_eq!(revision_resolve(&repo, "HEAD"), Ok(commits[2].clone()));
+        assert_eq!(revision_resolve(&repo, "@~2"), Ok(commits[0].clone()));
+        assert_eq!(revision_resolve(&repo, "master^^"), Ok(commits[0].clone()));
+        assert_eq!(revision_resolve(&repo, "HEAD^0"), Ok(commits[2].clone()));
+        assert!(revision_resolve(&repo, "HEAD~3").is_err());
+
+        let tree = object_find(&repo, &commits[1], Some("tree"), true).unwrap();
+        assert_eq!(revision_resolve(&repo, "HEAD^^{tree}"), Ok(tree));
+
+        // the file in the tree of an older commit, and the version currently staged
+        let old_blob = object_write(GitObject::Blob(GitBlob { data: Some("version 0".into()) }), None).unwrap();
+        assert_eq!(revision_resolve(&repo, "HEAD~2:inner/file.txt"), Ok(old_blob));
+        let staged_blob = revision_resolve(&repo, ":inner/file.txt").unwrap();
+        assert_eq!(revision_resolve(&repo, "HEAD:inner/file.txt"), Ok(staged_blob));
+
+        // a merge commit with two parents, ^2 picks the second
+        let merge = GitCommit::create(
+            revision_resolve(&repo, "HEAD^{tree}").unwrap(),
+            vec![commits[2].clone(), commits[0].clone()],
+            "rust_git <test@example.com> 0 +0000".to_string(),
+            "Merge".to_string()
+        );
+        let merge_sha = object_write(GitObject::Commit(merge), Some(&repo)).unwrap();
+        let mut merge_ref = Ref::new("refs/heads/merged".to_string());
+        merge_ref.add_target(RefType::Direct(merge_sha));
+        merge_ref.write(&repo).unwrap();
+        assert_eq!(revision_resolve(&repo, "merged^2"), Ok(commits[0].clone()));
+        assert_eq!(revision_resolve(&repo, "merged^1~1"), Ok(commits[1].clone()));
+
+        // @{-1} looks back through the checkouts recorded in the HEAD reflog
+        let log_line = format!("{} {} rust_git <test@example.com> 0 +0000\tcheckout: moving from merged to master\n", commits[0], commits[2]);
+        fs::create_dir_all(tmp_dir.path().join(".git/logs")).unwrap();
+        fs::write(tmp_dir.path().join(".git/logs/HEAD"), log_line).unwrap();
+        assert_eq!(revision_resolve(&repo, "@{-1}^2"), Ok(commits[0].clone()));
+
+        tmp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::time::SystemTime;
+use std::path::Path;
+use walkdir::WalkDir;
+use crate::branch_utils::branch_get_active;
+use crate::ignore::Ignore;
+use crate::index::{Index, IndexEntry};
+use crate::object_utils::{object_find, tree_to_entries};
+use crate::repository::Repository;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileChange {
+    Added,
+    Modified,
+    Deleted
+}
+
+/// Which sides of a conflicted merge have a version of the path, from the stages in the index
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Unmerged {
+    BothModified,
+    BothAdded,
+    DeletedByUs,
+    DeletedByThem,
+    AddedByUs,
+    AddedByThem
+}
+
+/// The state of the worktree, made up of three comparisons:
+///     staged = differences between the HEAD tree and the index
+///     unstaged = differences between the index and the files in the worktree
+///     untracked = files in the worktree which are not in the index (and not ignored)
+/// plus any paths left conflicted by a merge, which are in none of the above
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub staged: Vec<(FileChange, String)>,
+    pub unstaged: Vec<(FileChange, String)>,
+    pub unmerged: Vec<(Unmerged, String)>,
+    pub untracked: Vec<String>
+}
+
+impl FileChange {
+    fn label(&self) -> &'static str {
+        match self {
+            FileChange::Added => "new file",
+            FileChange::Modified => "modified",
+            FileChange::Deleted => "deleted"
+        }
+    }
+
+    fn porcelain_code(&self) -> char {
+        match self {
+            FileChange::Added => 'A',
+            FileChange::Modified => 'M',
+            FileChange::Deleted => 'D'
+        }
+    }
+}
+
+impl Unmerged {
+    fn from_index(index: &Index, name: &str) -> Self {
+        let stages = (1..=3).map(|stage| index.get_stage(name, stage).is_some()).collect::<Vec<bool>>();
+
+        match stages.as_slice() {
+            [true, true, true] => Unmerged::BothModified,
+            [true, true, false] => Unmerged::DeletedByThem,
+            [true, false, true] => Unmerged::DeletedByUs,
+            [false, true, false] => Unmerged::AddedByUs,
+            [false, false, true] => Unmerged::AddedByThem,
+            _ => Unmerged::BothAdded
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Unmerged::BothModified => "both modified",
+            Unmerged::BothAdded => "both added",
+            Unmerged::DeletedByUs => "deleted by us",
+            Unmerged::DeletedByThem => "deleted by them",
+            Unmerged::AddedByUs => "added by us",
+            Unmerged::AddedByThem => "added by them"
+        }
+    }
+
+    fn porcelain_code(&self) -> &'static str {
+        match self {
+            Unmerged::BothModified => "UU",
+            Unmerged::BothAdded => "AA",
+            Unmerged::DeletedByUs => "DU",
+            Unmerged::DeletedByThem => "UD",
+            Unmerged::AddedByUs => "AU",
+            Unmerged::AddedByThem => "UA"
+        }
+    }
+}
+
+impl Status {
+    pub fn read(repo: &Repository) -> Result<Self, String> {
+        let index = Index::read(repo)?;
+
+        Ok(Status {
+            branch: branch_get_active(repo),
+            head: object_find(repo, "HEAD", None, true).ok(),
+            staged: Status::head_index_changes(repo, &index)?,
+            unstaged: Status::index_worktree_changes(repo, &index)?,
+            unmerged: index.conflicted_paths().into_iter().map(|name| (Unmerged::from_index(&index, &name), name)).collect(),
+            untracked: Status::untracked_files(repo, &index)
+        })
+    }
+
+    fn head_index_changes(repo: &Repository, index: &Index) -> Result<Vec<(FileChange, String)>, String> {
+        let mut head = tree_to_entries(repo, "HEAD")?;
+        let mut changes = Vec::new();
+
+        // conflicted paths are reported on their own
+        for name in index.conflicted_paths() {
+            head.remove(&name);
+        }
+
+        for entry in index.entries.iter().filter(|entry| entry.flag_stage == 0) {
+            match head.remove(&entry.name) {
+                Some((sha, mode)) if sha != entry.sha || mode != entry.mode() => changes.push((FileChange::Modified, entry.name.clone())),
+                Some(_) => {},
+                None => changes.push((FileChange::Added, entry.name.clone()))
+            }
+        }
+
+        changes.extend(head.into_keys().map(|name| (FileChange::Deleted, name)));
+        changes.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(changes)
+    }
+
+    fn index_worktree_changes(repo: &Repository, index: &Index) -> Result<Vec<(FileChange, String)>, String> {
+        let mut changes = Vec::new();
+        let index_modified = Index::modified(repo);
+        let filemode = repo.conf.contents.core.filemode;
+
+        for entry in index.entries.iter().filter(|entry| entry.flag_stage == 0) {
+            let path = Path::new(&repo.worktree).join(&entry.name);
+
+            // a symlink counts as present even if what it points at is not
+            let current = match fs::symlink_metadata(&path) {
+                Ok(metadata) if !metadata.is_dir() => IndexEntry::new(entry.sha.clone(), path.to_str().unwrap().to_string()),
+                _ => {
+                    changes.push((FileChange::Deleted, entry.name.clone()));
+                    continue;
+                }
+            };
+
+            let mode_changed = filemode && current.mode() != entry.mode();
+            if mode_changed || (!Status::entry_matches_stat(entry, &current, index_modified)
+                && repo.hash_worktree_file(&entry.name)?.as_ref() != Some(&entry.sha)) {
+                changes.push((FileChange::Modified, entry.name.clone()));
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// The fast path: if the metadata recorded when the file was staged still matches we assume the
+    /// contents do too, and only hash the file when it doesnt.
+    ///
+    /// A file changed in the same instant it was staged keeps its size and mtime, so like git we only
+    /// trust entries modified before the index was written. Any others are racily clean and get hashed
+    fn entry_matches_stat(entry: &IndexEntry, current: &IndexEntry, index_modified: Option<SystemTime>) -> bool {
+        let racy = index_modified.is_none_or(|index_time| entry.mtime >= index_time);
+
+        !racy && current.mtime == entry.mtime && current.time == entry.time && current.fsize == entry.fsize
+            && current.dev == entry.dev && current.ino == entry.ino && current.uid == entry.uid && current.gid == entry.gid
+    }
+
+    /// Walk the worktree for files not in the index. Like git, a directory with no tracked files
+    /// at all is reported once as 'dir/' rather than listing every file inside it
+    fn untracked_files(repo: &Repository, index: &Index) -> Vec<String> {
+        let worktree = Path::new(&repo.worktree);
+        let ignore = Ignore::read(repo);
+
+        let tracked: HashSet<&str> = index.entries.iter().map(|entry| entry.name.as_str()).collect();
+        let tracked_dirs: HashSet<&str> = index.entries.iter()
+            .flat_map(|entry| entry.name.match_indices('/').map(|(i, _)| &entry.name[..i]))
+            .collect();
+
+        let walker = WalkDir::new(worktree).sort_by_file_name().into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git");
+
+        let mut untracked = Vec::new();
+        for entry in walker.filter_map(|entry| entry.ok()).filter(|entry| !entry.file_type().is_dir()) {
+            let name = entry.path().strip_prefix(worktree).unwrap().to_str().unwrap().to_string();
+
+            if tracked.contains(name.as_str()) || ignore.check_ignore(name.clone()) == Some(true) {
+                continue;
+            }
+
+            let untracked_dir = name.match_indices('/')
+                .map(|(i, _)| &name[..i])
+                .find(|dir| !tracked_dirs.contains(dir));
+
+            let reported = match untracked_dir {
+                Some(dir) => format!("{}/", dir),
+                None => name.clone()
+            };
+
+            if untracked.last() != Some(&reported) {
+                untracked.push(reported);
+            }
+        }
+
+        untracked
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty() && self.unstaged.is_empty() && self.unmerged.is_empty() && self.untracked.is_empty()
+    }
+
+    /// The long form output of git status
+    pub fn long_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        match (&self.branch, &self.head) {
+            (Some(branch), _) => lines.push(format!("On branch {}", branch)),
+            (None, Some(head)) => lines.push(format!("HEAD detached at {}", &head[..7])),
+            (None, None) => lines.push("Not currently on any branch".to_string())
+        }
+
+        let sections = [
+            ("Changes to be committed:", &self.staged),
+            ("Changes not staged for commit:", &self.unstaged)
+        ];
+
+        for (title, changes) in sections {
+            if changes.is_empty() {
+                continue;
+            }
+
+            lines.push(String::new());
+            lines.push(title.to_string());
+            for (change, name) in changes {
+                lines.push(format!("\t{:<12}{}", format!("{}:", change.label()), name));
+            }
+        }
+
+        if !self.unmerged.is_empty() {
+            lines.push(String::new());
+            lines.push("Unmerged paths:".to_string());
+            for (unmerged, name) in &self.unmerged {
+                lines.push(format!("\t{:<17}{}", format!("{}:", unmerged.label()), name));
+            }
+        }
+
+        if !self.untracked.is_empty() {
+            lines.push(String::new());
+            lines.push("Untracked files:".to_string());
+            for name in &self.untracked {
+                lines.push(format!("\t{}", name));
+            }
+        }
+
+        if self.is_clean() {
+            lines.push("nothing to commit, working tree clean".to_string());
+        }
+
+        lines
+    }
+
+    /// The machine readable 'XY path' format of git status --porcelain, where X is the
+    /// staged change, Y the unstaged change and untracked files are shown as '?? path'
+    pub fn porcelain_lines(&self) -> Vec<String> {
+        let mut codes: BTreeMap<&str, (char, char)> = BTreeMap::new();
+
+        for (change, name) in &self.staged {
+            codes.entry(name).or_insert((' ', ' ')).0 = change.porcelain_code();
+        }
+
+        for (change, name) in &self.unstaged {
+            codes.entry(name).or_insert((' ', ' ')).1 = change.porcelain_code();
+        }
+
+        let mut lines: Vec<String> = codes.into_iter()
+            .map(|(name, (staged, unstaged))| format!("{}{} {}", staged, unstaged, name))
+            .collect();
+
+        lines.extend(self.unmerged.iter().map(|(unmerged, name)| format!("{} {}", unmerged.porcelain_code(), name)));
+        lines.extend(self.untracked.iter().map(|name| format!("?? {}", name)));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+    use tempdir::TempDir;
+    use crate::index::IndexEntry;
+    use crate::repository::Repository;
+    use crate::status::{FileChange, Status};
+
+    #[test]
+    fn status_staged_unstaged_untracked() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::write(tmp_dir.path().join(".gitignore"), "*.log").unwrap();
+        fs::write(tmp_dir.path().join("first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("second.txt"), "second file").unwrap();
+        fs::write(tmp_dir.path().join("third.txt"), "third file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.commit("First commit".to_string()).unwrap();
+
+        let status = Status::read(&repo).unwrap();
+        assert!(status.is_clean());
+        assert_eq!(status.branch, Some("master".to_string()));
+
+        // staged: a new file and a modification
+        fs::write(tmp_dir.path().join("new.txt"), "new file").unwrap();
+        fs::write(tmp_dir.path().join("first.txt"), "first file staged").unwrap();
+        repo.add(vec![tmp_dir.path().join("new.txt").to_str().unwrap().to_string()]).unwrap();
+        repo.add(vec![tmp_dir.path().join("first.txt").to_str().unwrap().to_string()]).unwrap();
+
+        // unstaged: a further change to the staged file, an edit with the same size and a deletion
+        fs::write(tmp_dir.path().join("first.txt"), "first file unstaged").unwrap();
+        fs::write(tmp_dir.path().join("second.txt"), "SECOND FILE").unwrap();
+        fs::remove_file(tmp_dir.path().join("third.txt")).unwrap();
+
+        // untracked: a file, a whole directory and an ignored file which shouldnt show
+        fs::write(tmp_dir.path().join("untracked.txt"), "untracked").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("dir/inner")).unwrap();
+        fs::write(tmp_dir.path().join("dir/inner/file.txt"), "untracked").unwrap();
+        fs::write(tmp_dir.path().join("dir/other.txt"), "untracked").unwrap();
+        fs::write(tmp_dir.path().join("debug.log"), "ignored").unwrap();
+
+        let status = Status::read(&repo).unwrap();
+        assert_eq!(status.staged, vec![(FileChange::Modified, "first.txt".to_string()), (FileChange::Added, "new.txt".to_string())]);
+        assert_eq!(status.unstaged, vec![
+            (FileChange::Modified, "first.txt".to_string()),
+            (FileChange::Modified, "second.txt".to_string()),
+            (FileChange::Deleted, "third.txt".to_string())
+        ]);
+        assert_eq!(status.untracked, vec!["dir/", "untracked.txt"]);
+
+        assert_eq!(status.porcelain_lines(), vec![
+            "MM first.txt",
+            "A  new.txt",
+            " M second.txt",
+            " D third.txt",
+            "?? dir/",
+            "?? untracked.txt"
+        ]);
+
+        // the staged ignore rules still apply once the file is deleted from the worktree
+        fs::remove_file(tmp_dir.path().join(".gitignore")).unwrap();
+        let status = Status::read(&repo).unwrap();
+        assert!(status.unstaged.contains(&(FileChange::Deleted, ".gitignore".to_string())));
+        assert_eq!(status.untracked, vec!["dir/", "untracked.txt"]);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn status_racily_clean_entries() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let path = tmp_dir.path().join("file.txt");
+        fs::write(&path, "contents").unwrap();
+
+        let entry = IndexEntry::new("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".to_string(), path.to_str().unwrap().to_string());
+        let current = IndexEntry::new(entry.sha.clone(), path.to_str().unwrap().to_string());
+
+        // the stat is only trusted if the file was last modified before the index was written
+        assert!(Status::entry_matches_stat(&entry, &current, Some(entry.mtime + Duration::from_secs(1))));
+        assert!(!Status::entry_matches_stat(&entry, &current, Some(entry.mtime)));
+        assert!(!Status::entry_matches_stat(&entry, &current, None));
+
+        let mut moved = current.clone();
+        moved.ino += 1;
+        assert!(!Status::entry_matches_stat(&entry, &moved, Some(entry.mtime + Duration::from_secs(1))));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn status_executable_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let mut repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        let script = tmp_dir.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        repo.add(vec![tmp_dir_string]).unwrap();
+        repo.commit("Script".to_string()).unwrap();
+        assert!(Status::read(&repo).unwrap().is_clean());
+
+        // losing the executable bit is only a change when the config says file modes matter
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(Status::read(&repo).unwrap().is_clean());
+        repo.conf.contents.core.filemode = true;
+        assert_eq!(Status::read(&repo).unwrap().unstaged, vec![(FileChange::Modified, "run.sh".to_string())]);
+
+        // once added the mode change is staged even though the contents are the same
+        repo.add(vec![script.to_str().unwrap().to_string()]).unwrap();
+        let status = Status::read(&repo).unwrap();
+        assert_eq!(status.staged, vec![(FileChange::Modified, "run.sh".to_string())]);
+        assert!(status.unstaged.is_empty());
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn status_nested_paths() {
+        let tmp_dir = TempDir::new("dummy_repo").unwrap();
+        let tmp_dir_string: String = tmp_dir.path().to_str().unwrap().into();
+        let repo = Repository::create(tmp_dir_string.clone()).unwrap();
+
+        fs::create_dir_all(tmp_dir.path().join("dir/sub")).unwrap();
+        fs::write(tmp_dir.path().join("dir/first.txt"), "first file").unwrap();
+        fs::write(tmp_dir.path().join("dir/sub/second.txt"), "second file").unwrap();
+        repo.add(vec![tmp_dir_string.clone()]).unwrap();
+        repo.commit("First commit".to_string()).unwrap();
+        assert!(Status::read(&repo).unwrap().is_clean());
+
+        fs::write(tmp_dir.path().join("dir/sub/new.txt"), "new file").unwrap();
+        repo.add(vec![tmp_dir.path().join("dir/sub/new.txt").to_str().unwrap().to_string()]).unwrap();
+        fs::write(tmp_dir.path().join("dir/sub/second.txt"), "second file changed").unwrap();
+
+        let status = Status::read(&repo).unwrap();
+        assert_eq!(status.staged, vec![(FileChange::Added, "dir/sub/new.txt".to_string())]);
+        assert_eq!(status.unstaged, vec![(FileChange::Modified, "dir/sub/second.txt".to_string())]);
+        assert!(status.untracked.is_empty());
+
+        tmp_dir.close().unwrap();
+    }
+}